@@ -0,0 +1,37 @@
+// Lexes generated sources of doubling size and prints the time per line, so
+// a regression back to quadratic lexing shows up as a growing per-line cost.
+// Run with `cargo bench`.
+
+use wally::lexer;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SNIPPET: &str = r#"// Adds two numbers together.
+add: func<int> = (a: int, b: int = 2) => {
+    /* block comment */ total: int = a + b;
+    name: string = "héllo wörld";
+    return total;
+}
+"#;
+
+fn main() {
+    let mut baseline: Option<f64> = None;
+
+    for lines in [1_000, 2_000, 4_000, 8_000, 16_000, 32_000, 64_000] {
+        let source: String = SNIPPET.repeat(lines / SNIPPET.lines().count());
+        let runs: u32 = 5;
+        let mut best: Duration = Duration::MAX;
+
+        for _ in 0..runs {
+            let source: String = source.clone();
+            let start: Instant = Instant::now();
+            let tokens = black_box(lexer::lex(source)).expect("benchmark source should lex");
+            best = best.min(start.elapsed());
+            black_box(tokens);
+        }
+
+        let per_line: f64 = best.as_nanos() as f64 / lines as f64;
+        let ratio: f64 = per_line / *baseline.get_or_insert(per_line);
+        println!("{:>6} lines: {:>10.3?} total, {:>7.1} ns/line, {:.2}x baseline", lines, best, per_line, ratio);
+    }
+}
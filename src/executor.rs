@@ -1,214 +1,2285 @@
-use crate::lexer::{lex};
-use crate::parser::{Program, Statement, Expression, Type, parse};
+use crate::lexer::{lex, NumberKind, Span};
+use crate::parser::{
+    parse, AssignOperator, Binary, BinaryOperator, Block, Call, ClassDeclaration, EnumDeclaration, Expression, For, Identifier, Import, ImportSpec, LambdaBody, Member, MemberKind,
+    New, Number, Param, Path, PostfixOperator, Program, Return, Statement, TemplateElement, Type, UnaryOperator, VariableDeclaration,
+};
+use std::cell::RefCell;
 use std::fs::File;
 use std::io::prelude::*;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
 pub struct Executor {
     pub functions: Vec<Function>,
     pub variables: Vec<Variable>,
+    pub classes: Vec<ClassDeclaration>,
+    pub enums: Vec<Enum>,
+    // Local scopes of the function that is currently running, innermost last.
+    scopes: Vec<Vec<Local>>,
+    // Modules imported under an alias, as (alias, path).
+    modules: Vec<(String, String)>,
+    // Built-in functions imported by name, as (name, module, function). The
+    // name differs from the function when it was renamed with `as`.
+    imported: Vec<(String, String, String)>,
+    // The imports marked with `export`, which files importing this one
+    // import as well.
+    exports: Vec<Import>,
+    // The namespace that code is currently running in, outermost first.
+    // Declarations are stored under their qualified names, such as
+    // `Lexer::Token`.
+    namespace: Vec<String>,
+    // Nullable variables that a null check has shown to hold a value in the
+    // code that is running.
+    narrowed: Vec<String>,
+    // The imported file the running code was declared in, or `None` for the
+    // file that was started.
+    file: Option<String>,
+    // Classes declared in imported files, as (qualified name, file).
+    class_files: Vec<(String, String)>,
+    // The types that the type parameters of the running generic function
+    // or class stand for, as (parameter, type).
+    type_arguments: Vec<(String, Type)>,
 }
 
-#[derive(Debug, PartialEq)]
+// An error that stops the program, such as calling a function that does not
+// exist or assigning a value of the wrong type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    // The innermost statement or expression that failed, in `file`.
+    pub span: Option<Span>,
+    pub file: Option<String>,
+}
+
+impl RuntimeError {
+    fn new(message: String) -> RuntimeError {
+        RuntimeError {
+            message,
+            span: None,
+            file: None,
+        }
+    }
+    // Locates an error that has not been located by a node nested deeper.
+    fn at(mut self, span: Span, file: &Option<String>) -> RuntimeError {
+        if self.span.is_none() {
+            self.span = Some(span);
+            self.file = file.clone();
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Returns a `RuntimeError` with a formatted message from the current function.
+macro_rules! fail {
+    ($($argument:tt)*) => {
+        return Err(RuntimeError::new(format!($($argument)*)))
+    };
+}
+
+// The modules that ship with the interpreter and the functions they export.
+const BUILTIN_MODULES: &[(&str, &[&str])] = &[("io", &["print", "println"])];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     pub name: String,
-    pub parameters: Vec<Parameter>,
+    pub type_parameters: Vec<String>,
+    pub parameters: Vec<Param>,
     pub body: Vec<Statement>,
     pub return_type: Type,
+    // The imported file the function was declared in.
+    pub file: Option<String>,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Parameter {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
     pub name: String,
     pub _type: Type,
-    pub optional: bool,
-    pub default_value: Option<Expression>,
+    pub value: Value,
+    pub immutable: bool,
+}
+
+// A local variable. Lambdas share the locals they capture with the scope
+// they were created in, so both see assignments made by the other.
+pub type Local = Rc<RefCell<Variable>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    // Numbers keep their kind, so arithmetic wraps and rounds at the width
+    // of their type.
+    Integer(i128, NumberKind),
+    Float(f64, NumberKind),
+    String(String),
+    Character(char),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Function(Box<Closure>),
+    // Objects are shared, so changes made through one reference are seen
+    // through all of them.
+    Object(Rc<RefCell<Object>>),
+    Enum(EnumValue),
+    Null,
+    Void,
 }
 
 #[derive(Debug, PartialEq)]
-pub struct Variable {
+pub struct Enum {
     pub name: String,
-    pub _type: Type,
-    pub value: Option<Expression>,
-    pub immutable: bool,
+    pub variants: Vec<EnumValue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumValue {
+    pub enum_: String,
+    pub variant: String,
+    // The arguments of a Java-style variant, bound to the enum's parameters.
+    pub fields: Vec<Variable>,
+}
+
+#[derive(Clone)]
+pub struct Object {
+    pub class: String,
+    // The types the type parameters of a generic class stand for in this
+    // object, as (parameter, type). `?` where they could not be inferred.
+    pub type_arguments: Vec<(String, Type)>,
+    // The constructor parameters followed by the fields of the class body.
+    // They are shared with the lambdas created in the class body and are in
+    // scope in its methods, so a field can be used by its bare name.
+    pub fields: Vec<Local>,
+}
+
+impl Object {
+    fn field(&self, name: &str) -> Option<Local> {
+        self.fields.iter().find(|field| field.borrow().name == name).cloned()
+    }
+}
+
+// Fields can hold the object itself, so they are compared by identity.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.class == other.class
+            && self.type_arguments == other.type_arguments
+            && self.fields.len() == other.fields.len()
+            && self.fields.iter().zip(&other.fields).all(|(left, right)| Rc::ptr_eq(left, right))
+    }
+}
+
+// Objects can refer to themselves, so fields are shown by their displayed
+// value, which names the class of an object instead of its fields.
+impl std::fmt::Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let fields = self.fields.iter().map(|field| {
+            let field = field.borrow();
+            (field.name.clone(), field.value.to_string())
+        });
+        f.debug_struct("Object")
+            .field("class", &self.class)
+            .field("type_arguments", &self.type_arguments)
+            .field("fields", &fields.collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+// A function used as a value: a lambda, or a named function referenced
+// by name.
+#[derive(Clone)]
+pub struct Closure {
+    // The type parameters of a generic function, which are bound when it is
+    // called.
+    pub type_parameters: Vec<String>,
+    // The types bound to type parameters so far: those of the object of a
+    // method, those in scope where a lambda was created and explicit type
+    // arguments.
+    pub type_arguments: Vec<(String, Type)>,
+    pub parameters: Vec<Param>,
+    pub body: Vec<Statement>,
+    // The local variables that were in scope when the lambda was created.
+    pub captured: Vec<Local>,
+    // What the function returns, checked when it does. `?` for lambdas that
+    // have not been stored with a type.
+    pub return_type: Type,
+    // The namespace the function was declared in, which names in its body
+    // are resolved against.
+    pub namespace: Vec<String>,
+    // The imported file the function was declared in.
+    pub file: Option<String>,
+}
+
+// A lambda can capture a variable that holds it, so captured variables are
+// compared by identity and shown by name.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_parameters == other.type_parameters
+            && self.type_arguments == other.type_arguments
+            && self.parameters == other.parameters
+            && self.body == other.body
+            && self.captured.len() == other.captured.len()
+            && self.captured.iter().zip(&other.captured).all(|(left, right)| Rc::ptr_eq(left, right))
+            && self.return_type == other.return_type
+            && self.namespace == other.namespace
+            && self.file == other.file
+    }
+}
+
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let captured: Vec<String> = self.captured.iter().map(|local| local.borrow().name.clone()).collect();
+        f.debug_struct("Closure")
+            .field("type_parameters", &self.type_parameters)
+            .field("type_arguments", &self.type_arguments)
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .field("captured", &captured)
+            .field("return_type", &self.return_type)
+            .field("namespace", &self.namespace)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Integer(value, _) => write!(f, "{}", value),
+            Value::Float(value, NumberKind::Float) => write!(f, "{}", *value as f32),
+            Value::Float(value, _) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Character(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(elements) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Function(closure) => write!(f, "<lambda({})>", closure.parameters.len()),
+            Value::Object(object) => write!(f, "{}", object.borrow().class),
+            Value::Enum(value) => write!(f, "{}", value.variant),
+            Value::Null => write!(f, "null"),
+            Value::Void => write!(f, "void"),
+        }
+    }
+}
+
+// How a statement finished, so loops and calls know whether to keep going.
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+// Where an assignment stores its value. The objects and indexes on the way
+// there are evaluated once, so `a[i++] += 1` reads and writes the same element.
+enum Place<'a> {
+    // An identifier or path.
+    Variable(&'a Expression),
+    Element(Box<Place<'a>>, Value),
+    Field(Rc<RefCell<Object>>, String),
+    // A value that is not stored anywhere, such as the result of a call.
+    Temporary(Value),
 }
+impl Place<'_> {
+    fn is_temporary(&self) -> bool {
+        match self {
+            Place::Temporary(_) => true,
+            Place::Element(object, _) => object.is_temporary(),
+            Place::Variable(_) | Place::Field(..) => false,
+        }
+    }
+}
+
+pub fn execute(program: Program) -> Result<Executor, RuntimeError> {
+    execute_file(program, None)
+}
+
+// Runs a program read from `file`, which is `None` for the file that was
+// started.
+fn execute_file(program: Program, file: Option<String>) -> Result<Executor, RuntimeError> {
+    let mut executor = Executor {
+        functions: Vec::new(),
+        variables: Vec::new(),
+        classes: Vec::new(),
+        enums: Vec::new(),
+        scopes: Vec::new(),
+        modules: Vec::new(),
+        imported: Vec::new(),
+        exports: Vec::new(),
+        namespace: Vec::new(),
+        narrowed: Vec::new(),
+        file,
+        class_files: Vec::new(),
+        type_arguments: Vec::new(),
+    };
 
-pub fn execute(program: Program) -> Executor {
-    let mut i = 0;
+    executor.run(&program.statements)?;
 
-    let mut functions: Vec<Function> = Vec::new();
-    let mut global_variables: Vec<Variable> = Vec::new();
+    Ok(executor)
+}
 
-    while i < program.statements.len() {
-        let statement = &program.statements[i];
+impl Executor {
+    // Runs the top level of a program or namespace, where declarations are
+    // global.
+    fn run(&mut self, statements: &[Statement]) -> Result<(), RuntimeError> {
+        for statement in statements {
+            self.run_statement(statement)
+                .map_err(|error| error.at(statement.span(), &self.file))?;
+        }
+        Ok(())
+    }
 
+    fn run_statement(&mut self, statement: &Statement) -> Result<(), RuntimeError> {
         match statement {
             Statement::FunctionDeclaration(function) => {
-                let name: String = function.name.clone();
-                let mut parameters: Vec<Parameter> = Vec::new();
-                for parameter in &function.parameters {
-                    parameters.push(Parameter {
-                        name: parameter.0.clone(),
-                        _type: parameter.1.clone(),
-                        optional: parameter.2,
-                        default_value: Some(parameter.3.clone()),
-                    });
-                }
+                let name: String = self.qualify(&function.name);
+                let parameters: Vec<Param> = function.parameters.clone();
 
-                let body: Vec<Statement> = function.body.clone();
+                let body: Vec<Statement> = function.body.statements.clone();
                 let return_type: Type = function.return_type.clone();
 
                 let function = Function {
                     name,
+                    type_parameters: function.type_parameters.clone(),
                     parameters,
                     body,
                     return_type,
+                    file: self.file.clone(),
                 };
 
-                functions.push(function);
+                self.functions.push(function);
+            }
+            Statement::ClassDeclaration(class) => {
+                let mut class = class.clone();
+                class.name = self.qualify(&class.name);
+                if let Some(file) = &self.file {
+                    self.class_files.push((class.name.clone(), file.clone()));
+                }
+                self.classes.push(class);
+            }
+            Statement::EnumDeclaration(declaration) => {
+                let declaration = self.declare_enum(declaration)?;
+                self.enums.push(declaration);
+            }
+            Statement::VariableDeclaration(variable) => {
+                let value: Value = self.evaluate(&variable.value)?;
+                let value: Value = self.initial_value(value, variable)?;
+                self.variables.push(Variable {
+                    name: self.qualify(&variable.name),
+                    _type: variable.type_.clone(),
+                    value,
+                    immutable: variable.immutable,
+                });
+            }
+            Statement::Namespace(namespace) => {
+                self.namespace.push(namespace.name.clone());
+                self.run(&namespace.body.statements)?;
+                self.namespace.pop();
+            }
+            Statement::Import(import) => self.import(import)?,
+            _ => {
+                if let Flow::Return(_) = self.execute_statement(statement)? {
+                    fail!("Return outside of a function");
+                }
+            }
+        }
+        Ok(())
+    }
 
-                i += 1;
+    fn import(&mut self, import: &Import) -> Result<(), RuntimeError> {
+        let path = import.path.clone();
+        if import.exported {
+            self.exports.push(import.clone());
+        }
+
+        if let ImportSpec::Alias(alias) = &import.spec {
+            self.modules.push((alias.name.clone(), path.clone()));
+        }
+
+        if let Some((_, functions)) = BUILTIN_MODULES.iter().find(|(module, _)| *module == path) {
+            if let ImportSpec::Named(names) = &import.spec {
+                for (name, rename) in names {
+                    if !functions.contains(&name.name.as_str()) {
+                        return Err(RuntimeError::new(format!("Module '{}' has no function '{}'", path, name.name)).at(name.span, &self.file));
+                    }
+                    let local = rename.as_ref().unwrap_or(name).name.clone();
+                    self.imported.push((local, path.clone(), name.name.clone()));
+                }
+            }
+        } else if path.ends_with(".wly") {
+            let current_dir = std::env::current_dir().unwrap();
+            let mut contents = String::new();
+            let read = File::open(current_dir.join(&path)).and_then(|mut file| file.read_to_string(&mut contents));
+            if let Err(error) = read {
+                fail!("Cannot read '{}': {}", path, error);
             }
-            Statement::FunctionCall(function) => {
-                let name: String = function.name.clone();
-                let mut parameters: Vec<Expression> = Vec::new();
-                let mut local_variables: Vec<Variable> = Vec::new();
-                for parameter in &function.arguments {
-                    parameters.push(parameter.clone());
+            // Errors in the imported file are reported where they are.
+            let in_file = |message: String, span: Span| RuntimeError {
+                message,
+                span: Some(span),
+                file: Some(path.clone()),
+            };
+            let tokens = match lex(contents) {
+                Ok(tokens) => tokens,
+                Err(errors) => return Err(in_file(errors[0].to_string(), errors[0].span())),
+            };
+            let program = match parse(&tokens) {
+                Ok(program) => program,
+                Err(errors) => return Err(in_file(errors[0].to_string(), errors[0].span)),
+            };
+            let mut imported = execute_file(program, Some(path.clone()))?;
+
+            // Renamed functions and variables are added under their new name
+            // as well.
+            if let ImportSpec::Named(names) = &import.spec {
+                for (name, rename) in names {
+                    let function = imported.functions.iter().find(|function| function.name == name.name);
+                    let variable = imported.variables.iter().find(|variable| variable.name == name.name);
+                    let declared = imported.classes.iter().any(|class| class.name == name.name)
+                        || imported.enums.iter().any(|declaration| declaration.name == name.name);
+                    let at = |message: String| Err(RuntimeError::new(message).at(name.span, &self.file));
+                    match (function, variable, rename) {
+                        (Some(function), _, Some(rename)) => {
+                            let function = Function {
+                                name: rename.name.clone(),
+                                ..function.clone()
+                            };
+                            imported.functions.push(function);
+                        }
+                        (None, Some(variable), Some(rename)) => {
+                            let variable = Variable {
+                                name: rename.name.clone(),
+                                ..variable.clone()
+                            };
+                            imported.variables.push(variable);
+                        }
+                        (None, None, Some(_)) if declared => return at(format!("Only functions and variables can be renamed, not '{}'", name.name)),
+                        (None, None, _) if !declared => return at(format!("Module '{}' has nothing named '{}'", path, name.name)),
+                        _ => {}
+                    }
                 }
+            }
 
-                let function = functions.iter().find(|f| f.name == name);
-                if function == None {
-                    panic!("Function '{}' not found", name);
+            for function in imported.functions {
+                self.functions.push(function);
+            }
+
+            for variable in imported.variables {
+                self.variables.push(variable);
+            }
+
+            self.classes.extend(imported.classes);
+            self.class_files.extend(imported.class_files);
+            self.enums.extend(imported.enums);
+
+            // What the file re-exports is imported here too.
+            for export in &imported.exports {
+                self.import(export)?;
+            }
+        } else {
+            fail!("Unsupported file type");
+        }
+        Ok(())
+    }
+
+    fn execute_block(&mut self, block: &Block) -> Result<Flow, RuntimeError> {
+        self.scopes.push(Vec::new());
+        let flow = self.execute_statements(&block.statements);
+        self.scopes.pop();
+        flow
+    }
+
+    fn execute_statements(&mut self, statements: &[Statement]) -> Result<Flow, RuntimeError> {
+        for statement in statements {
+            match self.execute_statement(statement)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // Runs a statement, locating the errors that nothing inside it located.
+    fn execute_statement(&mut self, statement: &Statement) -> Result<Flow, RuntimeError> {
+        self.execute_unlocated(statement)
+            .map_err(|error| error.at(statement.span(), &self.file))
+    }
+
+    fn execute_unlocated(&mut self, statement: &Statement) -> Result<Flow, RuntimeError> {
+        match statement {
+            Statement::VariableDeclaration(variable) => {
+                let value: Value = self.evaluate(&variable.value)?;
+                let value: Value = self.initial_value(value, variable)?;
+                self.declare(Variable {
+                    name: variable.name.clone(),
+                    _type: variable.type_.clone(),
+                    value,
+                    immutable: variable.immutable,
+                });
+            }
+            Statement::Return(ret) => {
+                let value: Value = match &ret.value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Void,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Statement::Block(block) => return self.execute_block(block),
+            Statement::If(if_) => {
+                if self.condition(&if_.condition)? {
+                    let narrowed = narrowing(&if_.condition, true);
+                    return self.with_narrowed(narrowed, |this| this.execute_block(&if_.body));
+                } else if let Some(else_body) = &if_.else_body {
+                    let narrowed = narrowing(&if_.condition, false);
+                    return self.with_narrowed(narrowed, |this| this.execute_statement(else_body));
                 }
-                let function = function.unwrap().clone();
+            }
+            Statement::While(while_) => {
+                while self.condition(&while_.condition)? {
+                    let narrowed = narrowing(&while_.condition, true);
+                    match self.with_narrowed(narrowed, |this| this.execute_block(&while_.body))? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            Statement::For(for_) => {
+                // Variables declared by the init live in a scope around the whole loop.
+                self.scopes.push(Vec::new());
+                let flow = self.execute_for(for_);
+                self.scopes.pop();
+                return flow;
+            }
+            Statement::ForIn(for_in) => {
+                let elements: Vec<Value> = match self.evaluate(&for_in.iterable)? {
+                    Value::Array(elements) => elements,
+                    Value::Map(elements) => elements.into_iter().map(|(key, _)| key).collect(),
+                    Value::String(value) => value.chars().map(Value::Character).collect(),
+                    value => fail!("Cannot iterate over '{}'", value),
+                };
+                for element in elements {
+                    let element = match &for_in.type_ {
+                        Some(type_) => match self.coerce(element, type_) {
+                            Some(element) => element,
+                            None => {
+                                let error = RuntimeError::new(format!("Loop variable '{}' is not of type {}", for_in.name, self.substitute(type_)));
+                                return Err(error.at(for_in.type_span.unwrap_or(for_in.span), &self.file));
+                            }
+                        },
+                        None => element,
+                    };
+                    self.scopes.push(Vec::new());
+                    self.declare(Variable {
+                        name: for_in.name.clone(),
+                        _type: match &for_in.type_ {
+                            Some(type_) => type_.clone(),
+                            // Lambdas have no type to infer.
+                            None => type_of(&element).unwrap_or(Type::Any),
+                        },
+                        value: element,
+                        immutable: false,
+                    });
+                    let flow = self.execute_block(&for_in.body);
+                    self.scopes.pop();
+                    match flow? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal | Flow::Continue => {}
+                    }
+                }
+            }
+            Statement::Assign(assign) => {
+                let place = self.place(&assign.target)?;
+                let value = match assign.operator {
+                    AssignOperator::Assign => self.evaluate(&assign.value)?,
+                    AssignOperator::BitwiseNot => match self.evaluate(&assign.value)? {
+                        Value::Integer(value, kind) => Value::Integer(wrap(!value, kind), kind),
+                        value => {
+                            let error = RuntimeError::new(format!("Cannot apply {:?} to '{}'", assign.operator, value));
+                            return Err(error.at(assign.operator_span, &self.file));
+                        }
+                    },
+                    operator => {
+                        // Like in C, the result is converted back to the
+                        // kind of number the target holds.
+                        let current = self.read(&place)?;
+                        let value = self.evaluate(&assign.value)?;
+                        let result = self
+                            .operate(compound_operator(operator), current.clone(), value)
+                            .map_err(|error| error.at(assign.operator_span, &self.file))?;
+                        match (result, current) {
+                            (Value::Integer(value, _), Value::Integer(_, kind)) => Value::Integer(wrap(value, kind), kind),
+                            (Value::Float(value, _), Value::Float(_, kind)) => Value::Float(round(value, kind), kind),
+                            (value, _) => value,
+                        }
+                    }
+                };
+                self.write(&place, value)?;
+            }
+            Statement::Break(_) => return Ok(Flow::Break),
+            Statement::Continue(_) => return Ok(Flow::Continue),
+            Statement::Expression(expression) => {
+                self.evaluate(expression)?;
+            }
+            // The parser only accepts these declarations at the top level.
+            _ => fail!("Cannot declare this inside a block"),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn initial_value(&self, value: Value, variable: &VariableDeclaration) -> Result<Value, RuntimeError> {
+        match self.coerce(value.clone(), &variable.type_) {
+            Some(value) => Ok(value),
+            None => {
+                let error = RuntimeError::new(format!("Cannot assign '{}' to '{}' of type {}", value, variable.name, self.substitute(&variable.type_)));
+                Err(error.at(variable.type_span, &self.file))
+            }
+        }
+    }
+
+    fn execute_for(&mut self, for_: &For) -> Result<Flow, RuntimeError> {
+        if let Some(init) = &for_.init {
+            self.execute_statement(init)?;
+        }
+        loop {
+            if let Some(condition) = &for_.condition {
+                if !self.condition(condition)? {
+                    break;
+                }
+            }
+            match self.execute_block(&for_.body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Normal | Flow::Continue => {}
+            }
+            if let Some(step) = &for_.step {
+                self.execute_statement(step)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
 
-                let mut param_list = Vec::new();
-                for (i, parameter) in function.parameters.iter().enumerate() {
-                    let value = if i < parameters.len() {
-                        parameters[i].clone()
-                    } else if parameter.default_value.is_some() {
-                        parameter.default_value.clone().unwrap()
-                    } else if parameter.optional {
-                        parameter.default_value.clone().unwrap()
-                    } else {
-                        panic!("Missing parameter '{}'", parameter.name);
+    fn call(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let closure = match self.function_value(&[name.to_string()]) {
+            Some(closure) => closure,
+            None => fail!("Function '{}' not found", name),
+        };
+        self.invoke(closure, arguments)
+    }
+
+    fn function_value(&self, path: &[String]) -> Option<Closure> {
+        let name = self.resolve(path, |name| self.functions.iter().any(|f| f.name == name))?;
+        let function = self.functions.iter().find(|f| f.name == name)?;
+        Some(Closure {
+            type_parameters: function.type_parameters.clone(),
+            type_arguments: Vec::new(),
+            parameters: function.parameters.clone(),
+            body: function.body.clone(),
+            captured: Vec::new(),
+            return_type: function.return_type.clone(),
+            namespace: namespace_of(&name),
+            file: function.file.clone(),
+        })
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        let mut segments = self.namespace.clone();
+        segments.push(name.to_string());
+        segments.join("::")
+    }
+
+    // Finds the qualified name a path refers to, looking in the current
+    // namespace first and then in each enclosing one.
+    fn resolve(&self, path: &[String], exists: impl Fn(&str) -> bool) -> Option<String> {
+        (0..=self.namespace.len()).rev().find_map(|depth| {
+            let mut segments = self.namespace[..depth].to_vec();
+            segments.extend_from_slice(path);
+            let name = segments.join("::");
+            exists(&name).then_some(name)
+        })
+    }
+
+    fn invoke(&mut self, closure: Closure, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        // Type parameters without an explicit type argument take the types
+        // of the arguments passed for them, or `?` if there are none.
+        let mut type_arguments = closure.type_arguments;
+        for (parameter, argument) in closure.parameters.iter().zip(&arguments) {
+            infer(&parameter.type_, argument, &closure.type_parameters, &mut type_arguments);
+        }
+        bind_rest(&closure.type_parameters, &mut type_arguments);
+
+        // A call only sees its own locals, the variables its lambda captured
+        // and the globals.
+        let caller_type_arguments = std::mem::replace(&mut self.type_arguments, type_arguments);
+        let caller_scopes = std::mem::take(&mut self.scopes);
+        let caller_namespace = std::mem::replace(&mut self.namespace, closure.namespace);
+        let caller_narrowed = std::mem::take(&mut self.narrowed);
+        let caller_file = std::mem::replace(&mut self.file, closure.file);
+        self.scopes.push(closure.captured);
+        self.scopes.push(Vec::new());
+
+        // The return type is checked in the namespace of the function.
+        let value = self
+            .declare_parameters(closure.parameters, arguments)
+            .and_then(|_| self.execute_statements(&closure.body))
+            .and_then(|flow| match flow {
+                Flow::Return(value) => self.coerce(value.clone(), &closure.return_type).ok_or_else(|| {
+                    RuntimeError::new(format!("Returned '{}', which is not of type {}", value, self.substitute(&closure.return_type)))
+                }),
+                // Falling off the end returns nothing, which only `void`
+                // and `?` functions may do.
+                _ => self.coerce(Value::Void, &closure.return_type).ok_or_else(|| {
+                    RuntimeError::new(format!("Missing a return of type {}", self.substitute(&closure.return_type)))
+                }),
+            });
+        self.scopes = caller_scopes;
+        self.namespace = caller_namespace;
+        self.narrowed = caller_narrowed;
+        self.file = caller_file;
+        self.type_arguments = caller_type_arguments;
+        value
+    }
+
+    // Binds the type parameters of a generic function to the type arguments
+    // it was called with, as in `identity<int>(5)`.
+    fn with_type_arguments(&self, mut closure: Closure, type_arguments: &[Type]) -> Result<Closure, RuntimeError> {
+        if type_arguments.is_empty() {
+            return Ok(closure);
+        }
+        if type_arguments.len() != closure.type_parameters.len() {
+            fail!("Expected {} type argument(s), found {}", closure.type_parameters.len(), type_arguments.len());
+        }
+        for (parameter, type_) in closure.type_parameters.iter().zip(type_arguments) {
+            closure.type_arguments.push((parameter.clone(), self.substitute(type_)));
+        }
+        Ok(closure)
+    }
+
+    fn invoke_method(&mut self, object: Rc<RefCell<Object>>, mut closure: Closure, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let class = object.borrow().class.clone();
+        closure.type_arguments.extend(object.borrow().type_arguments.iter().cloned());
+        // The fields come before the variables the closure captured, which
+        // are declared closer to its body and so shadow them.
+        let mut captured = object.borrow().fields.clone();
+        captured.append(&mut closure.captured);
+        closure.captured = captured;
+        closure.captured.push(Rc::new(RefCell::new(Variable {
+            name: "this".to_string(),
+            _type: named(&class),
+            value: Value::Object(object),
+            immutable: true,
+        })));
+        self.invoke(closure, arguments)
+    }
+
+    // Binds arguments to parameters in the innermost scope, falling back to
+    // the default values of the missing ones.
+    fn declare_parameters(&mut self, parameters: Vec<Param>, arguments: Vec<Value>) -> Result<(), RuntimeError> {
+        let mut arguments = arguments.into_iter();
+        for parameter in parameters {
+            let value = match arguments.next() {
+                Some(value) => value,
+                None => match &parameter.default_value {
+                    Some(default_value) => self.evaluate(default_value)?,
+                    None if parameter.optional => Value::Null,
+                    None => fail!("Missing parameter '{}'", parameter.name),
+                },
+            };
+
+            let value = match self.coerce(value, &parameter.type_) {
+                Some(value) => value,
+                None => {
+                    let error = RuntimeError::new(format!("Parameter '{}' is not of type {}", parameter.name, self.substitute(&parameter.type_)));
+                    return Err(error.at(parameter.type_span, &self.file));
+                }
+            };
+
+            self.declare(Variable {
+                name: parameter.name,
+                _type: parameter.type_,
+                value,
+                immutable: false,
+            });
+        }
+        Ok(())
+    }
+
+    fn construct(&mut self, new: &New) -> Result<Value, RuntimeError> {
+        let arguments: Vec<Value> = self.evaluate_all(&new.arguments)?;
+        let class = self
+            .resolve(&new.class.segments, |name| self.classes.iter().any(|class| class.name == name))
+            .and_then(|name| self.classes.iter().find(|class| class.name == name));
+        let class = match class {
+            Some(class) => class.clone(),
+            None => fail!("Class '{}' not found", new.class),
+        };
+
+        let mut type_arguments: Vec<(String, Type)> = Vec::new();
+        if !new.type_arguments.is_empty() {
+            if new.type_arguments.len() != class.type_parameters.len() {
+                fail!("Class '{}' expects {} type argument(s), found {}", class.name, class.type_parameters.len(), new.type_arguments.len());
+            }
+            for (parameter, type_) in class.type_parameters.iter().zip(&new.type_arguments) {
+                type_arguments.push((parameter.clone(), self.substitute(type_)));
+            }
+        }
+        for (parameter, argument) in class.parameters.iter().zip(&arguments) {
+            infer(&parameter.type_, argument, &class.type_parameters, &mut type_arguments);
+        }
+        bind_rest(&class.type_parameters, &mut type_arguments);
+
+        let object = Rc::new(RefCell::new(Object {
+            class: class.name.clone(),
+            type_arguments: type_arguments.clone(),
+            fields: Vec::new(),
+        }));
+
+        // Field initializers run like a function body, with `this` and the
+        // constructor parameters in scope.
+        let caller_scopes = std::mem::take(&mut self.scopes);
+        let caller_namespace = std::mem::replace(&mut self.namespace, namespace_of(&class.name));
+        let caller_narrowed = std::mem::take(&mut self.narrowed);
+        let class_file = self.class_file(&class.name);
+        let caller_file = std::mem::replace(&mut self.file, class_file);
+        let caller_type_arguments = std::mem::replace(&mut self.type_arguments, type_arguments);
+        self.scopes.push(Vec::new());
+        self.declare(Variable {
+            name: "this".to_string(),
+            _type: named(&class.name),
+            value: Value::Object(object.clone()),
+            immutable: true,
+        });
+        self.scopes.push(Vec::new());
+        let initialized = self.initialize(&class, &object, arguments);
+        self.scopes = caller_scopes;
+        self.namespace = caller_namespace;
+        self.narrowed = caller_narrowed;
+        self.file = caller_file;
+        self.type_arguments = caller_type_arguments;
+        initialized?;
+
+        Ok(Value::Object(object))
+    }
+
+    // Binds the constructor parameters and runs the field initializers of a
+    // new object.
+    fn initialize(&mut self, class: &ClassDeclaration, object: &Rc<RefCell<Object>>, arguments: Vec<Value>) -> Result<(), RuntimeError> {
+        self.declare_parameters(class.parameters.clone(), arguments)?;
+        object.borrow_mut().fields = self.scopes.last().unwrap().clone();
+
+        for field in &class.fields {
+            let value = without_this(self.evaluate(&field.value)?);
+            let Some(value) = self.coerce(value, &field.type_) else {
+                let error = RuntimeError::new(format!("Field '{}' is not of type {}", field.name, self.substitute(&field.type_)));
+                return Err(error.at(field.type_span, &self.file));
+            };
+            let field = Rc::new(RefCell::new(Variable {
+                name: field.name.clone(),
+                _type: self.substitute(&field.type_),
+                value,
+                immutable: field.immutable,
+            }));
+            object.borrow_mut().fields.push(field.clone());
+            self.scopes.last_mut().unwrap().push(field);
+        }
+        Ok(())
+    }
+
+    fn class_file(&self, class: &str) -> Option<String> {
+        self.class_files.iter().find(|(name, _)| name == class).map(|(_, file)| file.clone())
+    }
+
+    fn declare_enum(&mut self, declaration: &EnumDeclaration) -> Result<Enum, RuntimeError> {
+        let mut variants: Vec<EnumValue> = Vec::new();
+        for variant in &declaration.variants {
+            let arguments: Vec<Value> = self.evaluate_all(&variant.arguments)?;
+
+            let caller_scopes = std::mem::take(&mut self.scopes);
+            self.scopes.push(Vec::new());
+            let declared = self.declare_parameters(declaration.parameters.clone(), arguments);
+            let fields = unshare(&self.scopes.pop().unwrap());
+            self.scopes = caller_scopes;
+            declared.map_err(|error| error.at(variant.span, &self.file))?;
+
+            variants.push(EnumValue {
+                enum_: self.qualify(&declaration.name),
+                variant: variant.name.clone(),
+                fields,
+            });
+        }
+        Ok(Enum {
+            name: self.qualify(&declaration.name),
+            variants,
+        })
+    }
+
+    // Calls a method of an object: a field holding a lambda, a method of its
+    // class or a method inherited from `Object`.
+    fn call_object_method(&mut self, object: Rc<RefCell<Object>>, name: &str, type_arguments: &[Type], arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let field = object.borrow().field(name).map(|field| field.borrow().value.clone());
+        let closure = match field {
+            Some(Value::Function(closure)) => *closure,
+            Some(value) => fail!("'{}' is not a function", value),
+            None => match self.method(&object.borrow().class, name) {
+                Some(closure) => closure,
+                None => {
+                    return match name {
+                        "toString" => Ok(Value::String(object.borrow().class.clone())),
+                        "equals" => match arguments.first() {
+                            Some(Value::Object(other)) => Ok(Value::Boolean(Rc::ptr_eq(&object, other))),
+                            _ => Ok(Value::Boolean(false)),
+                        },
+                        _ => fail!("'{}' has no method '{}'", object.borrow().class, name),
                     };
+                }
+            },
+        };
+        let closure = self.with_type_arguments(closure, type_arguments)?;
+        self.invoke_method(object, closure, arguments)
+    }
+
+    fn method(&self, class: &str, name: &str) -> Option<Closure> {
+        let class = self.classes.iter().find(|declaration| declaration.name == class)?;
+        let method = class.methods.iter().find(|method| method.name == name)?;
+        Some(Closure {
+            type_parameters: method.type_parameters.clone(),
+            type_arguments: Vec::new(),
+            parameters: method.parameters.clone(),
+            body: method.body.statements.clone(),
+            captured: Vec::new(),
+            return_type: method.return_type.clone(),
+            namespace: namespace_of(&class.name),
+            file: self.class_file(&class.name),
+        })
+    }
 
-                    if parameter._type != match value {
-                        Expression::NumberLiteral(_) => Type::Int,
-                        Expression::StringLiteral(_) => Type::String,
-                        Expression::BooleanLiteral(_) => Type::Boolean,
-                        Expression::ArrayLiteral(_) => panic!("Arrays not implemented"),
-                        Expression::CharacterLiteral(_) => Type::Character,
-                        Expression::MapLiteral(_) => panic!("Maps not implemented"),
-                        _ => panic!("Invalid parameter type"),
-                    } {
-                        panic!("Parameter '{}' is not of type {:?}", parameter.name, parameter._type);
+    // Converts objects to strings with their `toString` method, so strings
+    // can be built from them.
+    fn stringify(&mut self, value: Value) -> Result<Value, RuntimeError> {
+        match value {
+            Value::Object(object) => self.call_object_method(object, "toString", &[], Vec::new()),
+            value => Ok(value),
+        }
+    }
+
+    fn stringify_all(&mut self, values: Vec<Value>) -> Result<Vec<Value>, RuntimeError> {
+        values.into_iter().map(|value| self.stringify(value)).collect()
+    }
+
+    fn evaluate_call(&mut self, call: &Call) -> Result<Value, RuntimeError> {
+        let arguments: Vec<Value> = self.evaluate_all(&call.arguments)?;
+
+        match &*call.callee {
+            Expression::Identifier(identifier) => {
+                let name: &str = &identifier.name;
+                if let Some(closure) = self.function_value(&[name.to_string()]) {
+                    let closure = self.with_type_arguments(closure, &call.type_arguments)?;
+                    return self.invoke(closure, arguments);
+                }
+                if let Some(callee) = self.with_variable(name, |variable| variable.value.clone()) {
+                    return self.call_value(callee, &call.type_arguments, arguments);
+                }
+                match self.imported.iter().find(|(imported, _, _)| imported == name).cloned() {
+                    Some((_, module, function)) => {
+                        let arguments = self.stringify_all(arguments)?;
+                        call_builtin(&module, &function, arguments)
+                    }
+                    None => fail!("Function '{}' not found", name),
+                }
+            }
+            Expression::Member(member) => {
+                // Functions of a module imported under an alias.
+                if let Expression::Identifier(object) = &*member.object {
+                    if let Some((_, path)) = self.modules.iter().find(|(alias, _)| *alias == object.name) {
+                        let path = path.clone();
+                        return self.call_module(&path, &member.name, arguments);
                     }
-                        
-                    param_list.push((parameter.name.clone(), value));
                 }
+                // A module imported in an expression is imported the first
+                // time, under its path as the alias.
+                if let Expression::Module(module) = &*member.object {
+                    if !self.modules.iter().any(|(alias, _)| *alias == module.path) {
+                        self.import(&Import {
+                            path: module.path.clone(),
+                            spec: ImportSpec::Alias(Identifier {
+                                name: module.path.clone(),
+                                span: module.span,
+                            }),
+                            exported: false,
+                            span: module.span,
+                        })?;
+                    }
+                    return self.call_module(&module.path, &member.name, arguments);
+                }
+
+                // Methods of built-in values. Methods that change the value
+                // store it back where it came from, if it came from anywhere.
+                if member.kind != MemberKind::Optional {
+                    self.check_not_null(&member.object)?;
+                }
+                let place = self.place(&member.object)?;
+                let receiver = self.read(&place)?;
+                if let (Value::Null, MemberKind::Optional) = (&receiver, member.kind) {
+                    return Ok(Value::Null);
+                }
+                if let Value::Object(object) = receiver {
+                    return self.call_object_method(object, &member.name, &call.type_arguments, arguments);
+                }
+                if let (Value::Array(elements), "forEach") = (&receiver, member.name.as_str()) {
+                    for element in elements {
+                        let callback = arguments.first().cloned().unwrap_or(Value::Void);
+                        self.call_value(callback, &[], vec![element.clone()])?;
+                    }
+                    return Ok(Value::Void);
+                }
+                let (result, updated) = call_method(receiver, &member.name, arguments)?;
+                match updated {
+                    Some(updated) if !place.is_temporary() => self.write(&place, updated)?,
+                    _ => {}
+                }
+                Ok(result)
+            }
+            callee => {
+                let callee = self.evaluate(callee)?;
+                self.call_value(callee, &call.type_arguments, arguments)
+            }
+        }
+    }
+
+    fn call_module(&mut self, path: &str, name: &str, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        if BUILTIN_MODULES.iter().any(|(module, _)| *module == path) {
+            let arguments = self.stringify_all(arguments)?;
+            return call_builtin(path, name, arguments);
+        }
+        self.call(name, arguments)
+    }
 
-                let mut j = 0;
+    fn call_value(&mut self, callee: Value, type_arguments: &[Type], arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        match callee {
+            Value::Function(closure) => {
+                let closure = self.with_type_arguments(*closure, type_arguments)?;
+                self.invoke(closure, arguments)
+            }
+            callee => fail!("'{}' is not a function", callee),
+        }
+    }
 
-                while j < function.parameters.len() {
-                    let parameter = &function.parameters[j];
+    fn declare(&mut self, mut variable: Variable) {
+        variable._type = self.substitute(&variable._type);
+        match self.scopes.last_mut() {
+            Some(scope) => scope.push(Rc::new(RefCell::new(variable))),
+            None => self.variables.push(variable),
+        }
+    }
 
-                    if parameter.optional && parameters.len() <= j {
-                        parameters.push(parameter.default_value.clone().unwrap());
+    // Runs `f` on the innermost variable called `name`, local or global.
+    fn with_variable<T>(&mut self, name: &str, f: impl FnOnce(&mut Variable) -> T) -> Option<T> {
+        let local: Option<Local> = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|variable| variable.borrow().name == name))
+            .cloned();
+        match local {
+            Some(local) => Some(f(&mut local.borrow_mut())),
+            None => self.find_global(&[name.to_string()]).map(f),
+        }
+    }
+
+    fn find_global(&mut self, path: &[String]) -> Option<&mut Variable> {
+        let name = self.resolve(path, |name| self.variables.iter().any(|variable| variable.name == name))?;
+        self.variables.iter_mut().rev().find(|variable| variable.name == name)
+    }
+
+    // Runs `f` on the variable an identifier or path refers to.
+    fn with_target<T>(&mut self, target: &Expression, f: impl FnOnce(&mut Variable) -> T) -> Result<T, RuntimeError> {
+        let (result, name) = match target {
+            Expression::Identifier(identifier) => (self.with_variable(&identifier.name, f), identifier.name.clone()),
+            Expression::Path(path) => (self.find_global(&path.segments).map(f), path.to_string()),
+            _ => fail!("Cannot assign to this expression"),
+        };
+        match result {
+            Some(result) => Ok(result),
+            None => fail!("Variable '{}' not found", name),
+        }
+    }
+
+    // Converts a value to a type, widening numbers where needed. Returns
+    // `None` if the value is not of the type or a number does not fit it.
+    fn coerce(&self, value: Value, type_: &Type) -> Option<Value> {
+        let substituted: Type;
+        let type_ = if self.type_arguments.is_empty() {
+            type_
+        } else {
+            substituted = self.substitute(type_);
+            &substituted
+        };
+        let value = match (value, type_) {
+            (value, Type::Any) => value,
+            (Value::Integer(value, _), type_) if type_.number_kind().is_some() => {
+                let kind = type_.number_kind()?;
+                if kind.is_float() {
+                    Value::Float(round(value as f64, kind), kind)
+                } else if wrap(value, kind) == value {
+                    Value::Integer(value, kind)
+                } else {
+                    return None;
+                }
+            }
+            (Value::Float(value, _), Type::Float) => Value::Float(round(value, NumberKind::Float), NumberKind::Float),
+            (Value::Float(value, _), Type::Double) => Value::Float(value, NumberKind::Double),
+            (value @ Value::String(_), Type::String) => value,
+            (value @ Value::Character(_), Type::Character) => value,
+            (value @ Value::Boolean(_), Type::Boolean) => value,
+            (Value::Object(object), Type::Named(path, arguments)) => {
+                let class = object.borrow().class.clone();
+                let name = self.resolve(&path.segments, |name| self.classes.iter().any(|declaration| declaration.name == name))?;
+                if name != class || !type_arguments_match(&object, arguments) {
+                    return None;
+                }
+                Value::Object(object)
+            }
+            (Value::Enum(value), Type::Named(path, _)) => {
+                let name = self.resolve(&path.segments, |name| self.enums.iter().any(|declaration| declaration.name == name))?;
+                if name != value.enum_ {
+                    return None;
+                }
+                Value::Enum(value)
+            }
+            (Value::Array(elements), Type::Array(element_type)) => Value::Array(
+                elements
+                    .into_iter()
+                    .map(|element| self.coerce(element, element_type))
+                    .collect::<Option<Vec<Value>>>()?,
+            ),
+            (Value::Map(elements), Type::Map(key_type, value_type)) => Value::Map(
+                elements
+                    .into_iter()
+                    .map(|(key, value)| Some((self.coerce(key, key_type)?, self.coerce(value, value_type)?)))
+                    .collect::<Option<Vec<(Value, Value)>>>()?,
+            ),
+            // Lambdas take on the return type of the variable they are stored
+            // in, which is checked when they return.
+            (Value::Function(mut closure), Type::Function(parameters, return_type)) => {
+                if let Some(parameters) = parameters {
+                    if parameters.len() != closure.parameters.len()
+                        || parameters.iter().zip(&closure.parameters).any(|(type_, parameter)| *type_ != parameter.type_)
+                    {
+                        return None;
                     }
+                }
+                if closure.return_type == Type::Any {
+                    closure.return_type = (**return_type).clone();
+                }
+                Value::Function(closure)
+            }
+            (Value::Null, Type::Optional(_)) => Value::Null,
+            (value, Type::Optional(type_)) => self.coerce(value, type_)?,
+            (Value::Void, Type::Void) => Value::Void,
+            _ => return None,
+        };
+        Some(value)
+    }
 
-                    j += 1;
+    // Replaces the type parameters in a type with the types they stand for
+    // in the running code.
+    fn substitute(&self, type_: &Type) -> Type {
+        let substitute = |type_: &Type| Box::new(self.substitute(type_));
+        match type_ {
+            Type::Named(path, arguments) => {
+                if let ([name], true) = (path.segments.as_slice(), arguments.is_empty()) {
+                    if let Some((_, bound)) = self.type_arguments.iter().rev().find(|(parameter, _)| parameter == name) {
+                        return bound.clone();
+                    }
                 }
+                let arguments = arguments.iter().map(|argument| self.substitute(argument)).collect();
+                Type::Named(path.clone(), arguments)
+            }
+            Type::Array(element) => Type::Array(substitute(element)),
+            Type::Map(key, value) => Type::Map(substitute(key), substitute(value)),
+            Type::Pointer(target) => Type::Pointer(substitute(target)),
+            Type::Optional(type_) => Type::Optional(substitute(type_)),
+            Type::Function(parameters, return_type) => Type::Function(
+                parameters
+                    .as_ref()
+                    .map(|parameters| parameters.iter().map(|parameter| self.substitute(parameter)).collect()),
+                substitute(return_type),
+            ),
+            type_ => type_.clone(),
+        }
+    }
 
-                let mut k = 0;
+    fn condition(&mut self, expression: &Expression) -> Result<bool, RuntimeError> {
+        match self.evaluate(expression)? {
+            Value::Boolean(value) => Ok(value),
+            value => fail!("Condition '{}' is not a boolean", value),
+        }
+    }
 
-                while k < function.body.len() {
-                    let statement = &function.body[k];
+    // Evaluates an expression, locating the errors that nothing inside it
+    // located.
+    fn evaluate(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        self.evaluate_unlocated(expression)
+            .map_err(|error| error.at(expression.span(), &self.file))
+    }
+
+    fn evaluate_all(&mut self, expressions: &[Expression]) -> Result<Vec<Value>, RuntimeError> {
+        expressions.iter().map(|expression| self.evaluate(expression)).collect()
+    }
 
-                    match statement {
-                        Statement::Return(expression) => {
-                            break;
+    fn evaluate_unlocated(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        let value = match expression {
+            Expression::ArrayLiteral(array) => Value::Array(self.evaluate_all(&array.elements)?),
+            Expression::MapLiteral(map) => Value::Map(
+                map.elements
+                    .iter()
+                    .map(|(key, value)| Ok((self.evaluate(key)?, self.evaluate(value)?)))
+                    .collect::<Result<Vec<(Value, Value)>, RuntimeError>>()?,
+            ),
+            Expression::NumberLiteral(number) => match number.value {
+                Number::Integer(value) => Value::Integer(wrap(value as i128, number.kind), number.kind),
+                Number::Float(value) => Value::Float(round(value, number.kind), number.kind),
+            },
+            Expression::StringLiteral(string) => Value::String(string.value.clone()),
+            Expression::Template(template) => {
+                let mut value = String::new();
+                for part in &template.parts {
+                    match part {
+                        TemplateElement::Text(text, _) => value.push_str(text),
+                        TemplateElement::Expression(expression) => {
+                            let part = self.evaluate(expression)?;
+                            value.push_str(&self.stringify(part)?.to_string());
                         }
-                        Statement::VariableDeclaration(variable) => {
-                            let name: String = variable.name.clone();
-                            let _type: Type = variable.type_.clone();
-                            let value: Option<Expression> = Some(variable.value.clone());
-                            let immutable: bool = variable.immutable;
+                    }
+                }
+                Value::String(value)
+            }
+            Expression::CharacterLiteral(character) => Value::Character(character.value),
+            Expression::BooleanLiteral(boolean) => Value::Boolean(boolean.value),
+            Expression::Null(_) => Value::Null,
+            Expression::Identifier(identifier) => match self.with_variable(&identifier.name, |variable| variable.value.clone()) {
+                Some(value) => value,
+                None => match self.function_value(std::slice::from_ref(&identifier.name)) {
+                    Some(closure) => Value::Function(Box::new(closure)),
+                    None => fail!("Variable '{}' not found", identifier.name),
+                },
+            },
+            Expression::Path(path) => self.evaluate_path(path)?,
+            Expression::Binary(binary) => self.evaluate_binary(binary)?,
+            Expression::Unary(unary) => {
+                if let UnaryOperator::Increment | UnaryOperator::Decrement = unary.operator {
+                    let place = self.place(&unary.operand)?;
+                    let amount = if unary.operator == UnaryOperator::Increment { 1 } else { -1 };
+                    let value = step(self.read(&place)?, amount)?;
+                    self.write(&place, value.clone())?;
+                    return Ok(value);
+                }
+                let operand = self.evaluate(&unary.operand)?;
+                match (unary.operator, operand) {
+                    (UnaryOperator::Negate, Value::Integer(value, kind)) => Value::Integer(wrap(value.wrapping_neg(), kind), kind),
+                    (UnaryOperator::Negate, Value::Float(value, kind)) => Value::Float(-value, kind),
+                    (UnaryOperator::Plus, value @ (Value::Integer(..) | Value::Float(..))) => value,
+                    (UnaryOperator::LogicalNot, Value::Boolean(value)) => Value::Boolean(!value),
+                    (UnaryOperator::BitwiseNot, Value::Integer(value, kind)) => Value::Integer(wrap(!value, kind), kind),
+                    (operator, value) => fail!("Cannot apply {:?} to '{}'", operator, value),
+                }
+            }
+            Expression::Postfix(postfix) => {
+                let place = self.place(&postfix.operand)?;
+                let value = self.read(&place)?;
+                let amount = match postfix.operator {
+                    PostfixOperator::Increment => 1,
+                    PostfixOperator::Decrement => -1,
+                };
+                self.write(&place, step(value.clone(), amount)?)?;
+                value
+            }
+            Expression::Grouping(grouping) => self.evaluate(&grouping.expression)?,
+            Expression::Call(call) => self.evaluate_call(call)?,
+            Expression::Member(member) => self.evaluate_member(member)?,
+            Expression::New(new) => self.construct(new)?,
+            Expression::Module(module) => fail!("Module '{}' can only be used to call its functions", module.path),
+            Expression::This(_) => match self.with_variable("this", |this| this.value.clone()) {
+                Some(this) => this,
+                None => fail!("'this' outside of a class"),
+            },
+            Expression::Index(index) => {
+                let object = self.evaluate(&index.object)?;
+                let key = self.evaluate(&index.index)?;
+                element(object, &key)?
+            }
+            Expression::Lambda(lambda) => {
+                let body: Vec<Statement> = match &lambda.body {
+                    LambdaBody::Expression(expression) => vec![Statement::Return(Return {
+                        value: Some((**expression).clone()),
+                        span: expression.span(),
+                    })],
+                    LambdaBody::Block(block) => block.statements.clone(),
+                };
+                Value::Function(Box::new(Closure {
+                    type_parameters: Vec::new(),
+                    type_arguments: self.type_arguments.clone(),
+                    parameters: lambda.parameters.clone(),
+                    body,
+                    captured: self.scopes.iter().flatten().cloned().collect(),
+                    return_type: Type::Any,
+                    namespace: self.namespace.clone(),
+                    file: self.file.clone(),
+                }))
+            }
+        };
+        Ok(value)
+    }
 
-                            let variable = Variable {
-                                name,
-                                _type,
-                                value,
-                                immutable,
-                            };
+    // Evaluates a path such as `Lexer::tokens`, `Lexer::lex` or
+    // `TokenType::STRING`.
+    fn evaluate_path(&mut self, path: &Path) -> Result<Value, RuntimeError> {
+        if let Some(variable) = self.find_global(&path.segments) {
+            return Ok(variable.value.clone());
+        }
+        if let Some(closure) = self.function_value(&path.segments) {
+            return Ok(Value::Function(Box::new(closure)));
+        }
+        if let Some((variant, enum_path)) = path.segments.split_last() {
+            let enum_path = Expression::Path(Path {
+                segments: enum_path.to_vec(),
+                span: path.span,
+            });
+            if let Some(variant) = self.enum_variant(&enum_path, variant)? {
+                return Ok(variant);
+            }
+        }
+        fail!("'{}' not found", path)
+    }
 
-                            local_variables.push(variable);
+    fn evaluate_member(&mut self, member: &Member) -> Result<Value, RuntimeError> {
+        if let Some(variant) = self.enum_variant(&member.object, &member.name)? {
+            return Ok(variant);
+        }
+        if member.kind != MemberKind::Optional {
+            self.check_not_null(&member.object)?;
+        }
 
-                            k += 1;
-                        }
-                        _ => panic!("Unhandled statement '{:?}'", statement),
-                    }
+        let object = self.evaluate(&member.object)?;
+        self.member_of(object, member)
+    }
 
-                    k += 1;
+    fn member_of(&self, object: Value, member: &Member) -> Result<Value, RuntimeError> {
+        let value = match object {
+            Value::Object(object) => {
+                let object = object.borrow();
+                match object.field(&member.name) {
+                    Some(field) => field.borrow().value.clone(),
+                    None => match self.method(&object.class, &member.name) {
+                        Some(closure) => Value::Function(Box::new(closure)),
+                        None => fail!("'{}' has no member '{}'", object.class, member.name),
+                    },
                 }
+            }
+            Value::Enum(value) => match value.fields.iter().find(|field| field.name == member.name) {
+                Some(field) => field.value.clone(),
+                None => fail!("'{}' has no field '{}'", value.variant, member.name),
+            },
+            Value::Null if member.kind == MemberKind::Optional => Value::Null,
+            value => fail!("'{}' has no member '{}'", value, member.name),
+        };
+        Ok(value)
+    }
 
-                i += 1;
+    // Members of a nullable variable can only be used where a null check has
+    // shown that it holds a value, or through `?.`.
+    fn check_not_null(&mut self, object: &Expression) -> Result<(), RuntimeError> {
+        let Expression::Identifier(identifier) = object else {
+            return Ok(());
+        };
+        if self.narrowed.contains(&identifier.name) {
+            return Ok(());
+        }
+        if self.with_variable(&identifier.name, |variable| matches!(variable._type, Type::Optional(_))) == Some(true) {
+            fail!("'{}' may be null, so check it first or use '?.'", identifier.name);
+        }
+        Ok(())
+    }
+
+    fn with_narrowed<T>(&mut self, names: Vec<String>, run: impl FnOnce(&mut Self) -> T) -> T {
+        let length = self.narrowed.len();
+        self.narrowed.extend(names);
+        let result = run(self);
+        self.narrowed.truncate(length);
+        result
+    }
+
+    // Looks up variants such as `TokenType.STRING`, unless a variable
+    // shadows the enum.
+    fn enum_variant(&mut self, object: &Expression, name: &str) -> Result<Option<Value>, RuntimeError> {
+        let path = match object {
+            Expression::Identifier(identifier) if self.with_variable(&identifier.name, |_| ()).is_none() => {
+                vec![identifier.name.clone()]
             }
-            Statement::VariableDeclaration(variable) => {
-                let name: String = variable.name.clone();
-                let _type: Type = variable.type_.clone();
-                let value: Option<Expression> = Some(variable.value.clone());
-                let immutable: bool = variable.immutable;
+            Expression::Path(path) if self.find_global(&path.segments).is_none() => path.segments.clone(),
+            _ => return Ok(None),
+        };
+        let Some(name_of_enum) = self.resolve(&path, |name| self.enums.iter().any(|declaration| declaration.name == name)) else {
+            return Ok(None);
+        };
+        let declaration = self.enums.iter().find(|declaration| declaration.name == name_of_enum).unwrap();
+        match declaration.variants.iter().find(|variant| variant.variant == name) {
+            Some(variant) => Ok(Some(Value::Enum(variant.clone()))),
+            None => fail!("Enum '{}' has no variant '{}'", declaration.name, name),
+        }
+    }
 
-                let variable = Variable {
-                    name,
-                    _type,
-                    value,
-                    immutable,
+    fn place<'a>(&mut self, target: &'a Expression) -> Result<Place<'a>, RuntimeError> {
+        let place = match target {
+            Expression::Identifier(_) | Expression::Path(_) => Place::Variable(target),
+            Expression::Index(index) => {
+                let object = self.place(&index.object)?;
+                let key = self.evaluate(&index.index)?;
+                Place::Element(Box::new(object), key)
+            }
+            Expression::Member(member) => {
+                if let Some(variant) = self.enum_variant(&member.object, &member.name)? {
+                    return Ok(Place::Temporary(variant));
+                }
+                match self.evaluate(&member.object)? {
+                    Value::Object(object) => Place::Field(object, member.name.clone()),
+                    value => Place::Temporary(self.member_of(value, member)?),
+                }
+            }
+            _ => Place::Temporary(self.evaluate(target)?),
+        };
+        Ok(place)
+    }
+
+    fn read(&mut self, place: &Place) -> Result<Value, RuntimeError> {
+        match place {
+            Place::Variable(target) => self.evaluate(target),
+            Place::Element(object, key) => {
+                let object = self.read(object)?;
+                element(object, key)
+            }
+            Place::Field(object, name) => {
+                let object = object.borrow();
+                match object.field(name) {
+                    Some(field) => Ok(field.borrow().value.clone()),
+                    None => fail!("'{}' has no field '{}'", object.class, name),
+                }
+            }
+            Place::Temporary(value) => Ok(value.clone()),
+        }
+    }
+
+    fn write(&mut self, place: &Place, value: Value) -> Result<(), RuntimeError> {
+        match place {
+            Place::Variable(target) => {
+                let (name, type_, immutable) = self.with_target(target, |variable| (variable.name.clone(), variable._type.clone(), variable.immutable))?;
+                if immutable {
+                    fail!("Cannot assign to constant '{}'", name);
+                }
+                let value = match self.coerce(value.clone(), &type_) {
+                    Some(value) => value,
+                    None => fail!("Cannot assign '{}' to '{}' of type {}", value, name, type_),
+                };
+                self.with_target(target, |variable| variable.value = value)?;
+            }
+            Place::Element(object, key) => {
+                let updated = match self.read(object)? {
+                    Value::Array(mut elements) => {
+                        let i = array_index(key, elements.len())?;
+                        elements[i] = value;
+                        Value::Array(elements)
+                    }
+                    Value::Map(mut entries) => {
+                        match entries.iter_mut().find(|(entry, _)| values_equal(entry, key)) {
+                            Some(entry) => entry.1 = value,
+                            None => entries.push((key.clone(), value)),
+                        }
+                        Value::Map(entries)
+                    }
+                    object => fail!("Cannot index into '{}'", object),
+                };
+                self.write(object, updated)?;
+            }
+            Place::Field(object, name) => {
+                // The value may hold the object itself, which `coerce`
+                // borrows, so neither is borrowed around it.
+                let field = object.borrow().field(name);
+                let Some(field) = field else {
+                    fail!("'{}' has no field '{}'", object.borrow().class, name);
+                };
+                let (type_, immutable) = {
+                    let field = field.borrow();
+                    (field._type.clone(), field.immutable)
                 };
+                if immutable {
+                    fail!("Cannot assign to constant '{}'", name);
+                }
+                let value = match self.coerce(value.clone(), &type_) {
+                    Some(value) => value,
+                    None => fail!("Cannot assign '{}' to '{}' of type {}", value, name, type_),
+                };
+                field.borrow_mut().value = without_this(value);
+            }
+            Place::Temporary(_) => fail!("Cannot assign to this expression"),
+        }
+        Ok(())
+    }
+
+    fn evaluate_binary(&mut self, binary: &Binary) -> Result<Value, RuntimeError> {
+        // `&&`, `||` and `??` only evaluate their right side when they have
+        // to. The null checks on the left of `&&` and `||` hold on the right.
+        match binary.operator {
+            BinaryOperator::LogicalAnd => {
+                let value = self.condition(&binary.left)?
+                    && self.with_narrowed(narrowing(&binary.left, true), |this| this.condition(&binary.right))?;
+                return Ok(Value::Boolean(value));
+            }
+            BinaryOperator::LogicalOr => {
+                let value = self.condition(&binary.left)?
+                    || self.with_narrowed(narrowing(&binary.left, false), |this| this.condition(&binary.right))?;
+                return Ok(Value::Boolean(value));
+            }
+            BinaryOperator::NullCoalescing => {
+                return match self.evaluate(&binary.left)? {
+                    Value::Null => self.evaluate(&binary.right),
+                    value => Ok(value),
+                };
+            }
+            _ => {}
+        }
+
+        let left = self.evaluate(&binary.left)?;
+        let right = self.evaluate(&binary.right)?;
+        self.operate(binary.operator, left, right)
+    }
+
+    // Applies a binary operator, converting objects added to strings with
+    // their `toString` method.
+    fn operate(&mut self, operator: BinaryOperator, mut left: Value, mut right: Value) -> Result<Value, RuntimeError> {
+        if operator == BinaryOperator::Add {
+            if matches!(left, Value::String(_)) {
+                right = self.stringify(right)?;
+            } else if matches!(right, Value::String(_)) {
+                left = self.stringify(left)?;
+            }
+        }
+        binary_operation(operator, left, right)
+    }
+}
 
-                global_variables.push(variable);
+fn binary_operation(operator: BinaryOperator, left: Value, right: Value) -> Result<Value, RuntimeError> {
+    use BinaryOperator::*;
 
-                i += 1;
+    let value = match (operator, left, right) {
+        (Add, Value::String(left), right) => Value::String(left + &right.to_string()),
+        (Add, left, Value::String(right)) => Value::String(left.to_string() + &right),
+        (Equal, left, right) => Value::Boolean(values_equal(&left, &right)),
+        (NotEqual, left, right) => Value::Boolean(!values_equal(&left, &right)),
+        (Divide | Modulo, Value::Integer(..), Value::Integer(0, _)) => fail!("Division by zero"),
+        (operator, Value::Integer(left, left_kind), Value::Integer(right, right_kind)) => {
+            let kind = wider(left_kind, right_kind);
+            let integer = |value: i128| Value::Integer(wrap(value, kind), kind);
+            let shift = right.rem_euclid(kind.bits() as i128) as u32;
+            match operator {
+                Add => integer(left.wrapping_add(right)),
+                Subtract => integer(left.wrapping_sub(right)),
+                Multiply => integer(left.wrapping_mul(right)),
+                Divide => integer(left.wrapping_div(right)),
+                Modulo => integer(left.wrapping_rem(right)),
+                LessThan => Value::Boolean(left < right),
+                LessThanOrEqual => Value::Boolean(left <= right),
+                GreaterThan => Value::Boolean(left > right),
+                GreaterThanOrEqual => Value::Boolean(left >= right),
+                BitwiseAnd => integer(left & right),
+                BitwiseOr => integer(left | right),
+                BitwiseXor => integer(left ^ right),
+                LeftShift => integer(left.wrapping_shl(shift)),
+                RightShift => integer(left >> shift),
+                UnsignedRightShift => integer((unsigned(left, kind) >> shift) as i128),
+                _ => fail!("Cannot apply {:?} to '{}' and '{}'", operator, left, right),
+            }
+        }
+        (operator, left @ (Value::Integer(..) | Value::Float(..)), right @ (Value::Integer(..) | Value::Float(..))) => {
+            let kind = float_kind(&left, &right);
+            let float = |value: f64| Value::Float(round(value, kind), kind);
+            let (left, right) = (as_float(&left), as_float(&right));
+            match operator {
+                Add => float(left + right),
+                Subtract => float(left - right),
+                Multiply => float(left * right),
+                Divide => float(left / right),
+                Modulo => float(left % right),
+                LessThan => Value::Boolean(left < right),
+                LessThanOrEqual => Value::Boolean(left <= right),
+                GreaterThan => Value::Boolean(left > right),
+                GreaterThanOrEqual => Value::Boolean(left >= right),
+                _ => fail!("Cannot apply {:?} to '{}' and '{}'", operator, left, right),
             }
-            Statement::Import(import) => {
-                let methods = import.methods.clone();
-                let path = import.path.clone();
+        }
+        (operator, Value::Character(left), Value::Character(right)) => match operator {
+            LessThan => Value::Boolean(left < right),
+            LessThanOrEqual => Value::Boolean(left <= right),
+            GreaterThan => Value::Boolean(left > right),
+            GreaterThanOrEqual => Value::Boolean(left >= right),
+            _ => fail!("Cannot apply {:?} to '{}' and '{}'", operator, left, right),
+        },
+        (operator, left, right) => fail!("Cannot apply {:?} to '{}' and '{}'", operator, left, right),
+    };
+    Ok(value)
+}
 
-                if path.ends_with(".wly") {
-                    let current_dir = std::env::current_dir().unwrap();
-                    let mut file = File::open(current_dir.join(path)).expect("File not found");
-                    let mut contents = String::new();
-                    file.read_to_string(&mut contents)
-                        .expect("Something went wrong reading the file");
-                    let tokens = lex(contents);
-                    let program = parse(&tokens);
-                    let executor = execute(program);
+// Lambdas stored in an object get `this` when they are called as a method,
+// so they must not keep the `this` they were created with.
+fn without_this(value: Value) -> Value {
+    match value {
+        Value::Function(mut closure) => {
+            closure.captured.retain(|variable| variable.borrow().name != "this");
+            Value::Function(closure)
+        }
+        value => value,
+    }
+}
 
-                    for function in executor.functions {
-                        functions.push(function);
-                    }
+fn call_builtin(module: &str, name: &str, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match (module, name) {
+        ("io", "print") => {
+            for argument in arguments {
+                print!("{}", argument);
+            }
+        }
+        ("io", "println") => {
+            for argument in arguments {
+                print!("{}", argument);
+            }
+            println!();
+        }
+        _ => fail!("Module '{}' has no function '{}'", module, name),
+    }
+    Ok(Value::Void)
+}
+
+// Calls a method of a built-in value. Returns the result and, for methods
+// that change the receiver, its new value.
+fn call_method(receiver: Value, name: &str, arguments: Vec<Value>) -> Result<(Value, Option<Value>), RuntimeError> {
+    let argument = |i: usize| match arguments.get(i) {
+        Some(argument) => Ok(argument.clone()),
+        None => Err(RuntimeError::new(format!("Missing argument {} of '{}'", i + 1, name))),
+    };
 
-                    for variable in executor.variables {
-                        global_variables.push(variable);
+    match receiver {
+        Value::String(value) => {
+            let result = match name {
+                "length" => Value::Integer(value.chars().count() as i128, NumberKind::Int),
+                "charAt" => {
+                    let characters: Vec<char> = value.chars().collect();
+                    Value::Character(characters[array_index(&argument(0)?, characters.len())?])
+                }
+                "indexOf" => {
+                    let needle = argument(0)?.to_string();
+                    match value.find(&needle) {
+                        Some(byte) => Value::Integer(value[..byte].chars().count() as i128, NumberKind::Int),
+                        None => Value::Integer(-1, NumberKind::Int),
                     }
-                } else {
-                    panic!("Unsupported file type");
                 }
+                "replace" => Value::String(value.replace(&argument(0)?.to_string(), &argument(1)?.to_string())),
+                "split" => Value::Array(
+                    value
+                        .split(&argument(0)?.to_string())
+                        .map(|part| Value::String(part.to_string()))
+                        .collect(),
+                ),
+                "toUppercase" => Value::String(value.to_uppercase()),
+                "toLowercase" => Value::String(value.to_lowercase()),
+                "trim" => Value::String(value.trim().to_string()),
+                "toString" => Value::String(value),
+                _ => fail!("Unknown method 'string#{}'", name),
+            };
+            Ok((result, None))
+        }
+        Value::Array(mut elements) => {
+            let result = match name {
+                "length" => Value::Integer(elements.len() as i128, NumberKind::Int),
+                "get" => elements[array_index(&argument(0)?, elements.len())?].clone(),
+                "contains" => {
+                    let needle = argument(0)?;
+                    Value::Boolean(elements.iter().any(|element| values_equal(element, &needle)))
+                }
+                "indexOf" => {
+                    let needle = argument(0)?;
+                    match elements.iter().position(|element| values_equal(element, &needle)) {
+                        Some(i) => Value::Integer(i as i128, NumberKind::Int),
+                        None => Value::Integer(-1, NumberKind::Int),
+                    }
+                }
+                "copy" => Value::Array(elements.clone()),
+                "toString" => Value::String(Value::Array(elements.clone()).to_string()),
+                _ => {
+                    match name {
+                        "append" => elements.push(argument(0)?),
+                        "remove" => {
+                            let i = array_index(&argument(0)?, elements.len())?;
+                            elements.remove(i);
+                        }
+                        "insert" => {
+                            let i = array_index(&argument(0)?, elements.len() + 1)?;
+                            elements.insert(i, argument(1)?);
+                        }
+                        "clear" => elements.clear(),
+                        "reverse" => elements.reverse(),
+                        "sort" => {
+                            // The first comparison that fails is reported once
+                            // sorting is done.
+                            let mut failed: Option<RuntimeError> = None;
+                            elements.sort_by(|left, right| {
+                                match binary_operation(BinaryOperator::LessThan, left.clone(), right.clone()) {
+                                    Ok(Value::Boolean(true)) => std::cmp::Ordering::Less,
+                                    Err(error) => {
+                                        failed.get_or_insert(error);
+                                        std::cmp::Ordering::Equal
+                                    }
+                                    _ if values_equal(left, right) => std::cmp::Ordering::Equal,
+                                    _ => std::cmp::Ordering::Greater,
+                                }
+                            });
+                            if let Some(error) = failed {
+                                return Err(error);
+                            }
+                        }
+                        _ => fail!("Unknown method 'array#{}'", name),
+                    }
+                    return Ok((Value::Void, Some(Value::Array(elements))));
+                }
+            };
+            Ok((result, None))
+        }
+        Value::Map(mut entries) => {
+            let position = |key: &Value, entries: &[(Value, Value)]| entries.iter().position(|(entry, _)| values_equal(entry, key));
+            let result = match name {
+                "length" => Value::Integer(entries.len() as i128, NumberKind::Int),
+                "keys" => Value::Array(entries.iter().map(|(key, _)| key.clone()).collect()),
+                "values" => Value::Array(entries.iter().map(|(_, value)| value.clone()).collect()),
+                "get" => match position(&argument(0)?, &entries) {
+                    Some(i) => entries[i].1.clone(),
+                    None => fail!("Key '{}' not found", argument(0)?),
+                },
+                "containsKey" => Value::Boolean(position(&argument(0)?, &entries).is_some()),
+                "containsValue" => {
+                    let needle = argument(0)?;
+                    Value::Boolean(entries.iter().any(|(_, value)| values_equal(value, &needle)))
+                }
+                "indexOfKey" => Value::Integer(position(&argument(0)?, &entries).map_or(-1, |i| i as i128), NumberKind::Int),
+                "indexOfValue" => {
+                    let needle = argument(0)?;
+                    let position = entries.iter().position(|(_, value)| values_equal(value, &needle));
+                    Value::Integer(position.map_or(-1, |i| i as i128), NumberKind::Int)
+                }
+                "toString" => Value::String(Value::Map(entries.clone()).to_string()),
+                _ => {
+                    match name {
+                        "append" => match position(&argument(0)?, &entries) {
+                            Some(i) => entries[i].1 = argument(1)?,
+                            None => entries.push((argument(0)?, argument(1)?)),
+                        },
+                        "remove" => {
+                            if let Some(i) = position(&argument(0)?, &entries) {
+                                entries.remove(i);
+                            }
+                        }
+                        "clear" => entries.clear(),
+                        _ => fail!("Unknown method 'map#{}'", name),
+                    }
+                    return Ok((Value::Void, Some(Value::Map(entries))));
+                }
+            };
+            Ok((result, None))
+        }
+        value => match name {
+            "toString" => Ok((Value::String(value.to_string()), None)),
+            "equals" => Ok((Value::Boolean(values_equal(&value, &argument(0)?)), None)),
+            _ => fail!("'{}' has no method '{}'", value, name),
+        },
+    }
+}
 
-                i += 1;
+// The variables that a condition shows are not null when it evaluates to
+// `when`, such as `x` in `x != null`.
+fn narrowing(condition: &Expression, when: bool) -> Vec<String> {
+    match condition {
+        Expression::Binary(binary) => match (binary.operator, &*binary.left, &*binary.right, when) {
+            (BinaryOperator::NotEqual, Expression::Identifier(identifier), Expression::Null(_), true)
+            | (BinaryOperator::NotEqual, Expression::Null(_), Expression::Identifier(identifier), true)
+            | (BinaryOperator::Equal, Expression::Identifier(identifier), Expression::Null(_), false)
+            | (BinaryOperator::Equal, Expression::Null(_), Expression::Identifier(identifier), false) => {
+                vec![identifier.name.clone()]
+            }
+            (BinaryOperator::LogicalAnd, left, right, true) | (BinaryOperator::LogicalOr, left, right, false) => {
+                let mut names = narrowing(left, when);
+                names.extend(narrowing(right, when));
+                names
             }
-            _ => panic!("Unhandled statement '{:?}'", statement),
+            _ => Vec::new(),
+        },
+        Expression::Grouping(grouping) => narrowing(&grouping.expression, when),
+        Expression::Unary(unary) if unary.operator == UnaryOperator::LogicalNot => narrowing(&unary.operand, !when),
+        _ => Vec::new(),
+    }
+}
+
+// The element of an array, string or map at `key`.
+fn element(object: Value, key: &Value) -> Result<Value, RuntimeError> {
+    let value = match object {
+        Value::Array(elements) => {
+            let i = array_index(key, elements.len())?;
+            elements[i].clone()
+        }
+        Value::String(value) => {
+            let characters: Vec<char> = value.chars().collect();
+            Value::Character(characters[array_index(key, characters.len())?])
         }
+        Value::Map(entries) => match entries.into_iter().find(|(entry, _)| values_equal(entry, key)) {
+            Some((_, value)) => value,
+            None => fail!("Key '{}' not found", key),
+        },
+        value => fail!("Cannot index into '{}'", value),
+    };
+    Ok(value)
+}
+
+// Copies of the variables of a scope, such as the fields of an enum variant.
+fn unshare(scope: &[Local]) -> Vec<Variable> {
+    scope.iter().map(|variable| variable.borrow().clone()).collect()
+}
+
+fn array_index(index: &Value, length: usize) -> Result<usize, RuntimeError> {
+    match index {
+        Value::Integer(i, _) if *i >= 0 && (*i as usize) < length => Ok(*i as usize),
+        index => fail!("Index '{}' out of bounds for length {}", index, length),
+    }
+}
+
+// The binary operator a compound assignment such as `+=` applies.
+fn compound_operator(operator: AssignOperator) -> BinaryOperator {
+    match operator {
+        AssignOperator::Add => BinaryOperator::Add,
+        AssignOperator::Subtract => BinaryOperator::Subtract,
+        AssignOperator::Multiply => BinaryOperator::Multiply,
+        AssignOperator::Divide => BinaryOperator::Divide,
+        AssignOperator::Modulo => BinaryOperator::Modulo,
+        AssignOperator::BitwiseAnd => BinaryOperator::BitwiseAnd,
+        AssignOperator::BitwiseOr => BinaryOperator::BitwiseOr,
+        AssignOperator::BitwiseXor => BinaryOperator::BitwiseXor,
+        AssignOperator::LeftShift => BinaryOperator::LeftShift,
+        AssignOperator::RightShift => BinaryOperator::RightShift,
+        AssignOperator::UnsignedRightShift => BinaryOperator::UnsignedRightShift,
+        AssignOperator::Assign | AssignOperator::BitwiseNot => unreachable!(),
+    }
+}
+
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Integer(left, _), Value::Integer(right, _)) => left == right,
+        (Value::Integer(..) | Value::Float(..), Value::Integer(..) | Value::Float(..)) => as_float(left) == as_float(right),
+        (Value::Array(left), Value::Array(right)) => {
+            left.len() == right.len() && left.iter().zip(right).all(|(left, right)| values_equal(left, right))
+        }
+        (Value::Map(left), Value::Map(right)) => {
+            left.len() == right.len()
+                && left
+                    .iter()
+                    .zip(right)
+                    .all(|((left_key, left_value), (right_key, right_value))| {
+                        values_equal(left_key, right_key) && values_equal(left_value, right_value)
+                    })
+        }
+        (Value::Object(left), Value::Object(right)) => Rc::ptr_eq(left, right),
+        _ => left == right,
+    }
+}
+
+fn as_float(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value, _) => *value as f64,
+        Value::Float(value, _) => *value,
+        _ => unreachable!(),
+    }
+}
+
+fn step(value: Value, amount: i128) -> Result<Value, RuntimeError> {
+    match value {
+        Value::Integer(value, kind) => Ok(Value::Integer(wrap(value.wrapping_add(amount), kind), kind)),
+        Value::Float(value, kind) => Ok(Value::Float(round(value + amount as f64, kind), kind)),
+        value => fail!("Cannot increment or decrement '{}'", value),
+    }
+}
+
+// Wraps an integer around to the range of its kind, like a cast in C.
+fn wrap(value: i128, kind: NumberKind) -> i128 {
+    let bits = kind.bits();
+    if bits >= 128 {
+        return value;
+    }
+    let value = value & ((1 << bits) - 1);
+    if kind.is_signed() && value >> (bits - 1) == 1 {
+        value - (1 << bits)
+    } else {
+        value
+    }
+}
+
+// The bits of an integer as an unsigned number of the width of its kind.
+fn unsigned(value: i128, kind: NumberKind) -> u128 {
+    match kind.bits() {
+        128 => value as u128,
+        bits => (value & ((1 << bits) - 1)) as u128,
+    }
+}
+
+// Rounds a float to the precision of its kind.
+fn round(value: f64, kind: NumberKind) -> f64 {
+    match kind {
+        NumberKind::Float => value as f32 as f64,
+        _ => value,
     }
+}
 
-    println!("{:#?}", functions);
-    println!("{:#?}", global_variables);
+// The kind both integers are converted to before an operation: the wider
+// one, or the unsigned one if they are equally wide.
+fn wider(left: NumberKind, right: NumberKind) -> NumberKind {
+    match left.bits().cmp(&right.bits()) {
+        std::cmp::Ordering::Greater => left,
+        std::cmp::Ordering::Less => right,
+        std::cmp::Ordering::Equal if left.is_signed() => right,
+        std::cmp::Ordering::Equal => left,
+    }
+}
 
-    return Executor {
-        functions,
-        variables: global_variables,
+// The kind of the result of an operation on a float: `double` if either
+// side is one, `float` otherwise.
+fn float_kind(left: &Value, right: &Value) -> NumberKind {
+    if matches!(left, Value::Float(_, NumberKind::Double)) || matches!(right, Value::Float(_, NumberKind::Double)) {
+        NumberKind::Double
+    } else {
+        NumberKind::Float
+    }
+}
+
+// The type parameter a type names, such as `T`, if it names one of
+// `parameters`.
+fn type_parameter<'a>(type_: &'a Type, parameters: &[String]) -> Option<&'a String> {
+    match type_ {
+        Type::Named(path, arguments) if arguments.is_empty() => match path.segments.as_slice() {
+            [name] if parameters.contains(name) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Binds the type parameters a parameter type mentions to the types of the
+// parts of the argument they stand for, such as `T` to `int` for an
+// `array<T>` holding `[1, 2]`. Type parameters that are already bound keep
+// their type.
+fn infer(type_: &Type, value: &Value, parameters: &[String], bindings: &mut Vec<(String, Type)>) {
+    if let Some(name) = type_parameter(type_, parameters) {
+        if bindings.iter().any(|(parameter, _)| parameter == name) || *value == Value::Null {
+            return;
+        }
+        // Lambdas have no type to infer, so they leave the parameter unbound.
+        if let Ok(bound) = type_of(value) {
+            bindings.push((name.clone(), bound));
+        }
+        return;
+    }
+    match (type_, value) {
+        (Type::Array(element_type), Value::Array(elements)) => {
+            for element in elements {
+                infer(element_type, element, parameters, bindings);
+            }
+        }
+        (Type::Map(key_type, value_type), Value::Map(entries)) => {
+            for (key, value) in entries {
+                infer(key_type, key, parameters, bindings);
+                infer(value_type, value, parameters, bindings);
+            }
+        }
+        (Type::Optional(type_), value) => infer(type_, value, parameters, bindings),
+        // `Stack<T>` takes `T` from the type arguments of the object.
+        (Type::Named(_, arguments), Value::Object(object)) => {
+            for (argument, (_, bound)) in arguments.iter().zip(&object.borrow().type_arguments) {
+                if let Some(name) = type_parameter(argument, parameters) {
+                    if *bound != Type::Any && !bindings.iter().any(|(parameter, _)| parameter == name) {
+                        bindings.push((name.clone(), bound.clone()));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Erases the type parameters that nothing was bound to, so they accept any
+// value.
+fn bind_rest(parameters: &[String], bindings: &mut Vec<(String, Type)>) {
+    for parameter in parameters {
+        if !bindings.iter().any(|(name, _)| name == parameter) {
+            bindings.push((parameter.clone(), Type::Any));
+        }
+    }
+}
+
+// Whether an object fits the type arguments of a type such as `Stack<int>`.
+// Type parameters of the object that were erased take on the type argument.
+fn type_arguments_match(object: &Rc<RefCell<Object>>, arguments: &[Type]) -> bool {
+    if arguments.is_empty() {
+        return true;
+    }
+    let (fits, unbound) = {
+        let object = object.borrow();
+        if arguments.len() != object.type_arguments.len() {
+            return false;
+        }
+        let fits = arguments
+            .iter()
+            .zip(&object.type_arguments)
+            .all(|(argument, (_, bound))| *argument == Type::Any || *bound == Type::Any || argument == bound);
+        (fits, object.type_arguments.iter().any(|(_, bound)| *bound == Type::Any))
+    };
+    // The object is only borrowed mutably when there is something to bind,
+    // so checking an object that is being read elsewhere does not panic.
+    if fits && unbound {
+        for (argument, (_, bound)) in arguments.iter().zip(object.borrow_mut().type_arguments.iter_mut()) {
+            if *bound == Type::Any {
+                *bound = argument.clone();
+            }
+        }
+    }
+    fits
+}
+
+fn type_of(value: &Value) -> Result<Type, RuntimeError> {
+    let type_ = match value {
+        Value::Integer(_, kind) | Value::Float(_, kind) => Type::from(*kind),
+        Value::String(_) => Type::String,
+        Value::Character(_) => Type::Character,
+        Value::Boolean(_) => Type::Boolean,
+        Value::Array(elements) => match elements.first() {
+            Some(element) => Type::Array(Box::new(type_of(element)?)),
+            None => Type::Array(Box::new(Type::Int)),
+        },
+        Value::Map(elements) => match elements.first() {
+            Some((key, value)) => Type::Map(Box::new(type_of(key)?), Box::new(type_of(value)?)),
+            None => Type::Map(Box::new(Type::Int), Box::new(Type::Int)),
+        },
+        Value::Function(_) => fail!("Cannot infer the type of a lambda"),
+        Value::Object(object) => named(&object.borrow().class),
+        Value::Enum(value) => named(&value.enum_),
+        Value::Null => Type::Optional(Box::new(Type::Any)),
+        Value::Void => fail!("Void has no type"),
     };
-}
\ No newline at end of file
+    Ok(type_)
+}
+
+// The type of a value of a class or enum, from its qualified name.
+fn named(name: &str) -> Type {
+    Type::Named(
+        Path {
+            segments: name.split("::").map(String::from).collect(),
+            span: Span::default(),
+        },
+        Vec::new(),
+    )
+}
+
+// The namespace a qualified name such as `Lexer::lex` was declared in.
+fn namespace_of(name: &str) -> Vec<String> {
+    let mut segments: Vec<String> = name.split("::").map(String::from).collect();
+    segments.pop();
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<Executor, RuntimeError> {
+        let tokens = lex(source.to_string()).unwrap();
+        execute(parse(&tokens).unwrap())
+    }
+
+    // The global variables after running `source`, as `name = value`.
+    fn globals(source: &str) -> Vec<String> {
+        run(source)
+            .unwrap()
+            .variables
+            .iter()
+            .map(|variable| format!("{} = {}", variable.name, variable.value))
+            .collect()
+    }
+
+    #[test]
+    fn compound_assignment_evaluates_its_target_once() {
+        let source = "a: array<int> = [10, 20, 30];\ni: int = 0;\na[i++] += 1;\n";
+        assert_eq!(globals(source), vec!["a = [11, 20, 30]", "i = 1"]);
+    }
+
+    #[test]
+    fn nested_index_assignment_evaluates_each_index_once() {
+        let source = "m: array<array<int>> = [[1, 2], [3, 4]];\nj: int = 0;\nm[j++][j++] = 9;\nk: int = 0;\nm[k++][0]++;\n";
+        assert_eq!(globals(source), vec!["m = [[2, 9], [3, 4]]", "j = 2", "k = 1"]);
+    }
+
+    #[test]
+    fn lambdas_share_the_variables_they_capture() {
+        let source = "n: int = 0;\nm: int = 0;\ncount: func<int> = () => {\n    n: int = 0;\n    inc: lambda<void> = () => n += 1;\n    inc();\n    inc();\n    return n;\n}\nm = count();\n";
+        assert_eq!(globals(source), vec!["n = 0", "m = 2"]);
+    }
+
+    #[test]
+    fn fields_can_be_assigned_values_holding_their_object() {
+        let source = "Node: class = (value: int) => {\n    next: array<Node> = [];\n    link: lambda<void> = () => this->next = [this];\n}\nn: Node = new Node(1);\nn.link();\na: int = n.next.length();\nn.next = [n, n];\nb: int = n.next.length();\nBox<T>: class = (item: T) => {\n    boxes: array<Box<T>> = [];\n}\nx: Box<int> = new Box<int>(1);\nx.boxes = [x];\n";
+        assert_eq!(globals(source), vec!["n = Node", "a = 1", "b = 2", "x = Box"]);
+        assert!(format!("{:?}", run(source).unwrap().variables).contains("\"[Box]\""));
+    }
+
+    #[test]
+    fn lambdas_can_capture_the_variables_holding_them() {
+        let source = "keep: array<lambda<void>> = [];\nmk: func<void> = () => {\n    f: lambda<void> = () => {};\n    g: lambda<void> = () => f();\n    f = g;\n    keep.append(f);\n}\nmk();\n";
+        let executor = run(source).unwrap();
+        assert!(format!("{:?}", executor.variables).contains("captured: [\"f\"]"));
+        assert_eq!(executor.variables[0].value, executor.variables[0].value.clone());
+    }
+
+    #[test]
+    fn functions_must_return_a_value_of_their_type() {
+        let error = run("g: func<int> = () => { }\ng();\n").err().unwrap();
+        assert_eq!(error.message, "Missing a return of type int");
+        let source = "v: func<void> = () => { }\nv();\nl: lambda<void> = () => {};\nl();\nn: int = 0;\nf: func<int> = () => {\n    if (n == 0) {\n        return 1;\n    }\n}\nn = f();\n";
+        assert_eq!(globals(source), vec!["l = <lambda(0)>", "n = 1"]);
+    }
+
+    #[test]
+    fn class_bodies_see_fields_changed_after_construction() {
+        let source = "C: class = (value: string) => {\n    items: array<int> = [];\n    getValue: lambda<string> = () => value;\n    setValue: lambda<void> = (v: string) => this->value = v;\n    add: func<void> = (x: int) => {\n        items.append(x);\n        value = value + x;\n    }\n    count: func<int> = () => items.length();\n}\nc: C = new C(\"a\");\nc.setValue(\"b\");\nv: string = c.getValue();\nc.add(1);\nc.add(2);\nn: int = c.count();\nw: string = c.getValue();\n";
+        assert_eq!(globals(source), vec!["c = C", "v = b", "n = 2", "w = b12"]);
+    }
+
+    #[test]
+    fn compound_addition_converts_objects_like_addition() {
+        let source = "C: class = () => {\n    override toString: lambda<string> = () => \"made C\";\n}\nc: C = new C();\ns: string = \"v=\";\ns += c;\nt: string = \"v=\";\nt = t + c;\n";
+        assert_eq!(globals(source), vec!["c = C", "s = v=made C", "t = v=made C"]);
+    }
+
+    #[test]
+    fn type_errors_point_at_the_annotation() {
+        let source = "x: int = 1;\ny: array<int> = \"a\";\n";
+        let error = run(source).err().unwrap();
+        let span = error.span.unwrap();
+        assert_eq!((span.line, span.column, &source[span.start_byte..span.end_byte]), (2, 4, "array<int>"));
+    }
+
+    #[test]
+    fn enums_can_be_plain_or_carry_fields() {
+        let source = "Color: enum => RED, GREEN;\nTokenType: enum = (regex: string, priority: int = 1) => {\n    STRING(\"s\"),\n    NUMBER(\"[0-9]+\", 2),\n}\nc: Color = Color.GREEN;\nsame: boolean = c == Color.GREEN;\nt: TokenType = TokenType.NUMBER;\nr: string = TokenType.STRING.regex;\np: int = t.priority + TokenType.STRING.priority;\n";
+        assert_eq!(globals(source), vec!["c = GREEN", "same = true", "t = NUMBER", "r = s", "p = 3"]);
+        let error = run("Color: enum => RED;\nShape: enum => RED;\nc: Color = Shape.RED;\n").err().unwrap();
+        assert_eq!(error.message, "Cannot assign 'RED' to 'c' of type Color");
+        let error = run("Color: enum => RED;\nx: ? = Color.RED.regex;\n").err().unwrap();
+        assert_eq!(error.message, "'RED' has no field 'regex'");
+    }
+
+    #[test]
+    fn namespaces_resolve_paths_to_functions_and_types() {
+        let source = "A: namespace => {\n    T: class = (n: int) => {\n        get: lambda<int> = () => n;\n    }\n    count: int = 0;\n    B: namespace => {\n        f: func<int> = (x: int) => {\n            return x * 2;\n        }\n    }\n}\nx: int = A::B::f(21);\nts: array<A::T> = [new A::T(4)];\nn: int = ts[0].get();\nA::count += 5;\nk: int = A::count;\n";
+        assert_eq!(globals(source), vec!["A::count = 5", "x = 42", "ts = [A::T]", "n = 4", "k = 5"]);
+        let error = run("A: namespace => {\n    T: class = () => { }\n}\nts: array<A::T> = [1];\n").err().unwrap();
+        assert_eq!(error.message, "Cannot assign '[1]' to 'ts' of type array<A::T>");
+        let error = run("A: namespace => { }\nx: ? = A::f();\n").err().unwrap();
+        assert_eq!(error.message, "'A::f' not found");
+    }
+
+    #[test]
+    fn nullable_values_need_a_check_or_a_null_safe_operator() {
+        let source = "greet: func<string> = (name: string, title?: string) => {\n    if (title == null) {\n        return name;\n    }\n    return title + \" \" + name;\n}\na: string = greet(\"Ann\");\nb: string = greet(\"Ann\", \"Dr\");\ns: string? = null;\nn: int = s?.length() ?? -1;\ns = \"abc\";\nm: int = s?.length() ?? -1;\nl: int = 0;\nif (s != null) {\n    l = s.length();\n}\n";
+        assert_eq!(globals(source), vec!["a = Ann", "b = Dr Ann", "s = abc", "n = -1", "m = 3", "l = 3"]);
+        let error = run("s: string? = \"abc\";\nn: int = s.length();\n").err().unwrap();
+        assert_eq!(error.message, "'s' may be null, so check it first or use '?.'");
+        let error = run("s: string = null;\n").err().unwrap();
+        assert_eq!(error.message, "Cannot assign 'null' to 's' of type string");
+    }
+
+    #[test]
+    fn generic_type_arguments_are_inferred_or_checked() {
+        let source = "identity<T>: func<T> = (x: T) => x;\nStack<T>: class = (items: array<T>) => {\n    push: func<void> = (item: T) => {\n        items.append(item);\n    }\n    peek: func<T> = () => items[items.length() - 1];\n}\na: int = identity(1);\nb: string = identity<string>(\"b\");\ns: Stack<int> = new Stack([1]);\ns.push(2);\np: int = s.peek();\n";
+        assert_eq!(globals(source), vec!["a = 1", "b = b", "s = Stack", "p = 2"]);
+        let error = run("identity<T>: func<T> = (x: T) => x;\nx: ? = identity<int>(\"x\");\n").err().unwrap();
+        assert_eq!(error.message, "Parameter 'x' is not of type int");
+        let error = run("Stack<T>: class = (items: array<T>) => {\n    push: func<void> = (item: T) => items.append(item);\n}\ns: Stack<int> = new Stack<int>([]);\ns.push(\"x\");\n").err().unwrap();
+        assert_eq!(error.message, "Parameter 'item' is not of type int");
+    }
+
+    #[test]
+    fn arithmetic_wraps_to_the_width_and_sign_of_its_kind() {
+        let source = "a: byte = 250u8 + 10u8;\nb: short = 32767s + 1s;\nc: int = 2147483647 + 1;\nd: uint = 0u - 1u;\ne: int64 = 9223372036854775807i64 + 1i64;\nf: uint64 = 0u64 - 1u64;\ng: long = -170141183460469231731687303715884105727l - 2l;\nh: int = -8 >>> 28;\ni: float = 16777216f + 1f;\nj: double = 16777216.0 + 1.0;\nk: ? = 0.1f + 0.2f;\nl: ? = 0.1 + 0.2;\n";
+        assert_eq!(
+            globals(source),
+            vec![
+                "a = 4",
+                "b = -32768",
+                "c = -2147483648",
+                "d = 4294967295",
+                "e = -9223372036854775808",
+                "f = 18446744073709551615",
+                "g = 170141183460469231731687303715884105727",
+                "h = 15",
+                "i = 16777216",
+                "j = 16777217",
+                "k = 0.3",
+                "l = 0.30000000000000004",
+            ]
+        );
+        assert_eq!(wrap(300, NumberKind::Byte), 44);
+        assert_eq!(wrap(-1, NumberKind::Byte), 255);
+        assert_eq!(wrap(0x8000, NumberKind::Short), -0x8000);
+        assert_eq!(wrap(1 << 32, NumberKind::UInt), 0);
+        assert_eq!(wrap(i128::MIN, NumberKind::Long), i128::MIN);
+        assert_eq!(round(0.1, NumberKind::Float), 0.1f32 as f64);
+        assert_eq!(round(0.1, NumberKind::Double), 0.1);
+    }
+
+    #[test]
+    fn the_declaration_example_runs() {
+        run(include_str!("../tests/var_decl.wly")).unwrap();
+    }
+
+    #[test]
+    fn templates_convert_their_expressions_to_strings() {
+        let source = "C: class = () => {\n    override toString: lambda<string> = () => \"made C\";\n}\nn: int = 2;\nt: string = `n=${n * 2}, ${new C()}, ${[1, 2]}, ${`${n}${'!'}`}, \\${n}`;\n";
+        assert_eq!(globals(source), vec!["n = 2", "t = n=4, made C, [1, 2], 2!, ${n}"]);
+    }
+
+    #[test]
+    fn methods_only_store_changed_values_that_are_stored_somewhere() {
+        let source = "mk: func<array<int>> = () => { return [1]; }\nmk().append(2);\n[1].append(2);\ni: int = 0;\nb: array<array<int>> = [[1], [2]];\nb[i++].append(5);\n";
+        assert_eq!(globals(source), vec!["i = 1", "b = [[1, 5], [2]]"]);
+    }
+}
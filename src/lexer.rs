@@ -18,10 +18,12 @@ pub enum TokenType {
     // Literal values
     IdentifierLiteral,
     StringLiteral,
+    TemplateLiteral,
     CharLiteral,
     NumberLiteral,
     FloatLiteral,
     BooleanLiteral,
+    NullLiteral,
 
     // Operators
     Plus,
@@ -63,7 +65,10 @@ pub enum TokenType {
     FatArrow,
     Arrow,
     QuestionMark,
+    QuestionDot,
+    NullCoalescing,
     Colon,
+    DoubleColon,
     Comma,
     Semicolon,
     Dot,
@@ -77,479 +82,695 @@ pub enum TokenType {
     // Keywords
     Const,
     Import,
+    Export,
     Func,
     Lambda,
     Array,
     Map,
     Pointer,
     Return,
+    If,
+    Else,
+    While,
+    For,
+    Continue,
+    Break,
+    New,
+    Throw,
+    Override,
+    This,
+    Class,
+    Enum,
+    Namespace,
 
     // Special
+    DocComment,
     EndOfFile,
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub value: String,
+    pub span: Span,
+    // The exact source text of the token, before escapes are decoded.
+    pub text: String,
+    // Only filled in by `lex_lossless`.
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
+    // The pieces of a `TemplateLiteral`, empty for every other token.
+    pub parts: Vec<TemplatePart>,
+}
+// Each embedded `${...}` expression keeps its own tokens, ending with an
+// `EndOfFile` token at the closing brace. Text keeps the span of its source,
+// escapes included.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Text(String, Span),
+    Expression(Vec<Token>),
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+// Source text between tokens. A token owns the trivia that follows it up to
+// the end of its line; everything else leads the next token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start_byte: usize,
+    pub end_byte: usize,
     pub line: usize,
     pub column: usize,
 }
-pub fn lex(source: String) -> Vec<Token> {
-    let mut tokens: Vec<Token> = Vec::new();
-    let mut i: usize = 0;
-    let mut line: usize = 1;
-    let mut column: usize = 1;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberKind {
+    Int,
+    Int64,
+    UInt,
+    UInt64,
+    Short,
+    Long,
+    Byte,
+    Float,
+    Double,
+}
+impl NumberKind {
+    fn from_suffix(suffix: &str) -> Option<NumberKind> {
+        match suffix {
+            "i32" => Some(NumberKind::Int),
+            "i64" => Some(NumberKind::Int64),
+            "u" | "u32" => Some(NumberKind::UInt),
+            "u64" => Some(NumberKind::UInt64),
+            "s" | "i16" => Some(NumberKind::Short),
+            "l" | "i128" => Some(NumberKind::Long),
+            "u8" => Some(NumberKind::Byte),
+            "f" | "f32" => Some(NumberKind::Float),
+            "d" | "f64" => Some(NumberKind::Double),
+            _ => None,
+        }
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, NumberKind::Float | NumberKind::Double)
+    }
+    pub fn is_signed(&self) -> bool {
+        !matches!(self, NumberKind::UInt | NumberKind::UInt64 | NumberKind::Byte)
+    }
+    pub fn bits(&self) -> u32 {
+        match self {
+            NumberKind::Byte => 8,
+            NumberKind::Short => 16,
+            NumberKind::Int | NumberKind::UInt | NumberKind::Float => 32,
+            NumberKind::Int64 | NumberKind::UInt64 | NumberKind::Double => 64,
+            NumberKind::Long => 128,
+        }
+    }
+}
+// Splits a number literal (without `_` separators) into its radix, digits
+// and kind. Literals without a suffix are `int`, or `double` if they have a
+// fraction or exponent. Returns `None` for malformed literals.
+pub fn split_number_literal(value: &str) -> Option<(u32, &str, NumberKind)> {
+    let (radix, body): (u32, &str) = match value.get(..2) {
+        Some("0x" | "0X") => (16, &value[2..]),
+        Some("0b" | "0B") => (2, &value[2..]),
+        Some("0o" | "0O") => (8, &value[2..]),
+        _ => (10, value),
+    };
+
+    let mut end: usize = body.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(body.len());
+    if radix == 10 {
+        end = body.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(body.len());
+        if let Some(exponent) = body[end..].strip_prefix(['e', 'E']) {
+            let unsigned: &str = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+            let length: usize = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+            if length > 0 {
+                end = body.len() - unsigned.len() + length;
+            }
+        }
+    }
+    let (digits, suffix): (&str, &str) = body.split_at(end);
+
+    let float: bool = radix == 10 && digits.contains(['.', 'e', 'E']);
+    if digits.is_empty() || digits.ends_with('.') || digits.matches('.').count() > 1 {
+        return None;
+    }
+    if radix != 10 && !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+
+    let kind: NumberKind = match suffix {
+        "" if float => NumberKind::Double,
+        "" => NumberKind::Int,
+        _ => NumberKind::from_suffix(suffix)?,
+    };
+    if (float || radix != 10) && kind.is_float() != float {
+        return None;
+    }
+    Some((radix, digits, kind))
+}
+// Whether every `_` in a number literal is followed by a digit and follows
+// a digit or the radix prefix, as in `1_000` or `0x_FF`, so literals can't
+// end in `_` or double it.
+fn separates_digits(text: &str, radix: u32) -> bool {
+    let characters: Vec<char> = text.chars().collect();
+    let digit = |i: usize| characters.get(i).is_some_and(|c| c.is_digit(radix));
+    let after_prefix = |i: usize| radix != 10 && i == 2;
+    characters.iter().enumerate().all(|(i, c)| *c != '_' || ((after_prefix(i) || (i > 0 && digit(i - 1))) && digit(i + 1)))
+}
+// Each error covers the text it is about, from its start up to where the
+// lexer noticed the problem.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnexpectedCharacter { character: char, span: Span },
+    UnterminatedString { span: Span },
+    UnterminatedChar { span: Span },
+    UnterminatedTemplate { span: Span },
+    InvalidChar { value: String, span: Span },
+    InvalidEscape { sequence: String, span: Span },
+    UnterminatedBlockComment { span: Span },
+    InvalidNumber { value: String, span: Span },
+}
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedCharacter { span, .. }
+            | LexError::UnterminatedString { span }
+            | LexError::UnterminatedChar { span }
+            | LexError::UnterminatedTemplate { span }
+            | LexError::InvalidChar { span, .. }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::UnterminatedBlockComment { span }
+            | LexError::InvalidNumber { span, .. } => *span,
+        }
+    }
+}
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LexError::UnexpectedCharacter { character, .. } => write!(f, "unexpected character '{}'", character),
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            LexError::UnterminatedChar { .. } => write!(f, "unterminated char literal"),
+            LexError::UnterminatedTemplate { .. } => write!(f, "unterminated template literal"),
+            LexError::InvalidChar { value, .. } => write!(f, "char literal '{}' must contain exactly one character", value),
+            LexError::InvalidEscape { sequence, .. } => write!(f, "invalid escape sequence '{}'", sequence),
+            LexError::UnterminatedBlockComment { .. } => write!(f, "unterminated block comment"),
+            LexError::InvalidNumber { value, .. } => write!(f, "invalid number literal '{}'", value),
+        }
+    }
+}
+pub fn lex(source: String) -> Result<Vec<Token>, Vec<LexError>> {
+    run_lexer(&source, false)
+}
+// Like `lex`, but keeps whitespace and comments as trivia on the tokens so
+// `to_source` can rebuild the input exactly.
+pub fn lex_lossless(source: String) -> Result<Vec<Token>, Vec<LexError>> {
+    run_lexer(&source, true)
+}
+pub fn to_source(tokens: &[Token]) -> String {
+    let mut source: String = String::new();
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            source.push_str(&trivia.text);
+        }
+        source.push_str(&token.text);
+        for trivia in &token.trailing_trivia {
+            source.push_str(&trivia.text);
+        }
+    }
+    source
+}
+fn run_lexer(source: &str, lossless: bool) -> Result<Vec<Token>, Vec<LexError>> {
+    let mut lexer = Lexer {
+        cursor: Cursor {
+            source,
+            position: 0,
+            line: 1,
+            column: 1,
+        },
+        tokens: Vec::new(),
+        errors: Vec::new(),
+        lossless,
+        leading_trivia: Vec::new(),
+        trailing: false,
+    };
+
+    while let Some(c) = lexer.cursor.peek() {
+        let start: Cursor = lexer.cursor.clone();
+        if let Some(kind) = lexer.lex_trivia(c) {
+            lexer.push_trivia(kind, &start);
+        } else if let Some(token) = lexer.lex_token(c, &start) {
+            lexer.push_token(token);
+        }
+    }
+
+    let end: Cursor = lexer.cursor.clone();
+    let eof: Token = lexer.token(TokenType::EndOfFile, "".to_string(), &end);
+    lexer.push_token(eof);
+
+    if lexer.errors.is_empty() {
+        Ok(demote_stray_doc_comments(lexer.tokens, lossless))
+    } else {
+        Err(lexer.errors)
+    }
+}
+
+// Doc comments only stay `DocComment` tokens where they can document a
+// declaration: at the start of a statement or class member that declares
+// something. Anywhere else they are ordinary comments, kept as trivia of the
+// next token when lexing losslessly.
+fn demote_stray_doc_comments(tokens: Vec<Token>, lossless: bool) -> Vec<Token> {
+    let keep: Vec<bool> = (0..tokens.len())
+        .map(|index| tokens[index].token_type != TokenType::DocComment || documents_declaration(&tokens, index))
+        .collect();
+    let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut carried: Vec<Trivia> = Vec::new();
+    for (mut token, keep) in tokens.into_iter().zip(keep) {
+        if !keep && !lossless {
+            continue;
+        }
+        if !keep {
+            let kind: TriviaKind = if token.text.starts_with("///") {
+                TriviaKind::LineComment
+            } else {
+                TriviaKind::BlockComment
+            };
+            carried.append(&mut token.leading_trivia);
+            carried.push(Trivia {
+                kind,
+                text: token.text,
+                span: token.span,
+            });
+            carried.append(&mut token.trailing_trivia);
+            continue;
+        }
+        if !carried.is_empty() {
+            carried.append(&mut token.leading_trivia);
+            token.leading_trivia = std::mem::take(&mut carried);
+        }
+        result.push(token);
+    }
+    result
+}
+fn documents_declaration(tokens: &[Token], index: usize) -> bool {
+    let is_doc = |token: &&Token| token.token_type == TokenType::DocComment;
+    let mut before = tokens[..index].iter().rev().skip_while(is_doc);
+    let starts_statement = match before.next().map(|token| &token.token_type) {
+        None | Some(TokenType::Semicolon | TokenType::CloseBrace) => true,
+        // Only the brace of a block or body, not of a map literal.
+        Some(TokenType::OpenBrace) => matches!(
+            before.next().map(|token| &token.token_type),
+            None | Some(
+                TokenType::FatArrow
+                    | TokenType::CloseParenthesis
+                    | TokenType::Else
+                    | TokenType::Semicolon
+                    | TokenType::OpenBrace
+                    | TokenType::CloseBrace
+            )
+        ),
+        Some(_) => false,
+    };
+
+    let mut after = tokens[index..].iter().skip_while(is_doc);
+    let declares = match after.next().map(|token| &token.token_type) {
+        Some(TokenType::Const | TokenType::Override) => true,
+        Some(TokenType::IdentifierLiteral) => matches!(
+            after.next().map(|token| &token.token_type),
+            Some(TokenType::Colon | TokenType::LessThan)
+        ),
+        _ => false,
+    };
+    starts_statement && declares
+}
+
+// A position in the source. Cloning a cursor is cheap, so it doubles as the
+// start marker of the token currently being lexed.
+#[derive(Clone)]
+struct Cursor<'a> {
+    source: &'a str,
+    position: usize,
+    line: usize,
+    column: usize,
+}
+impl Cursor<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.position..].chars().nth(n)
+    }
+    fn bump(&mut self) -> Option<char> {
+        let c: char = self.peek()?;
+        self.position += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            return true;
+        }
+
+        false
+    }
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) -> &str {
+        let start: usize = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.source[start..self.position]
+    }
+    fn span_from(&self, start: &Cursor) -> Span {
+        Span {
+            start_byte: start.position,
+            end_byte: self.position,
+            line: start.line,
+            column: start.column,
+        }
+    }
+}
+
+struct Lexer<'a> {
+    cursor: Cursor<'a>,
+    tokens: Vec<Token>,
+    errors: Vec<LexError>,
+    lossless: bool,
+    leading_trivia: Vec<Trivia>,
+    // Whether trivia still belongs to the previous token's line.
+    trailing: bool,
+}
+impl Lexer<'_> {
+    fn token(&self, token_type: TokenType, value: String, start: &Cursor) -> Token {
+        Token {
+            token_type,
+            value,
+            span: self.cursor.span_from(start),
+            text: start.source[start.position..self.cursor.position].to_string(),
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+            parts: Vec::new(),
+        }
+    }
 
-    while i < source.len() {
-        let c: char = source.chars().nth(i).unwrap();
+    fn push_token(&mut self, mut token: Token) {
+        token.leading_trivia = std::mem::take(&mut self.leading_trivia);
+        self.tokens.push(token);
+        self.trailing = true;
+    }
 
+    fn push_trivia(&mut self, kind: TriviaKind, start: &Cursor) {
+        if !self.lossless {
+            return;
+        }
+
+        let trivia: Trivia = Trivia {
+            kind,
+            text: start.source[start.position..self.cursor.position].to_string(),
+            span: self.cursor.span_from(start),
+        };
+        match self.tokens.last_mut() {
+            Some(token) if self.trailing => token.trailing_trivia.push(trivia),
+            _ => self.leading_trivia.push(trivia),
+        }
+        if kind == TriviaKind::Newline {
+            self.trailing = false;
+        }
+    }
+
+    // `///` and `/** */` comments are doc comments, unless they are written
+    // as `////...` or `/**/`.
+    fn at_doc_comment(&self) -> bool {
+        match (self.cursor.peek_nth(1), self.cursor.peek_nth(2), self.cursor.peek_nth(3)) {
+            (Some('/'), Some('/'), next) => next != Some('/'),
+            (Some('*'), Some('*'), next) => next != Some('/') && next != Some('*'),
+            _ => false,
+        }
+    }
+
+    // Skips the rest of a block comment after its opening `/*`. Block
+    // comments nest, so every `/*` needs its own `*/`.
+    fn skip_block_comment(&mut self, start: &Cursor) {
+        let mut depth: usize = 1;
+        while depth > 0 {
+            match (self.cursor.peek(), self.cursor.peek_nth(1)) {
+                (None, _) => {
+                    self.errors.push(LexError::UnterminatedBlockComment { span: self.cursor.span_from(start) });
+                    return;
+                }
+                (Some('/'), Some('*')) => {
+                    self.cursor.bump();
+                    self.cursor.bump();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.cursor.bump();
+                    self.cursor.bump();
+                    depth -= 1;
+                }
+                _ => {
+                    self.cursor.bump();
+                }
+            }
+        }
+    }
+
+    // Lexes a doc comment after its first `/` into a `DocComment` token whose
+    // value is the comment text without the comment markers.
+    fn lex_doc_comment(&mut self, start: &Cursor) -> Option<Token> {
+        let value: String = if self.cursor.eat('/') {
+            self.cursor.bump();
+            let text: &str = self.cursor.eat_while(|c| c != '\n' && c != '\r');
+            text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
+        } else {
+            self.cursor.bump();
+            self.cursor.bump();
+            let errors: usize = self.errors.len();
+            self.skip_block_comment(start);
+            if self.errors.len() > errors {
+                return None;
+            }
+            let text: &str = &start.source[start.position + 3..self.cursor.position - 2];
+            text.lines()
+                .map(|line| {
+                    let line: &str = line.trim_start();
+                    let line: &str = line.strip_prefix('*').unwrap_or(line);
+                    line.strip_prefix(' ').unwrap_or(line).trim_end()
+                })
+                .collect::<Vec<&str>>()
+                .join("\n")
+                .trim()
+                .to_string()
+        };
+        Some(self.token(TokenType::DocComment, value, start))
+    }
+
+    // Consumes whitespace or a comment starting with `c`, if there is one.
+    fn lex_trivia(&mut self, c: char) -> Option<TriviaKind> {
         match c {
-            ' ' => {
-                i += 1;
+            ' ' | '\t' => {
+                self.cursor.eat_while(|c| c == ' ' || c == '\t');
+                Some(TriviaKind::Whitespace)
             }
-            '\t' => {
-                i += 1;
+            '\n' => {
+                self.cursor.bump();
+                Some(TriviaKind::Newline)
             }
             '\r' => {
-                i += 1;
+                self.cursor.bump();
+                if self.cursor.eat('\n') {
+                    Some(TriviaKind::Newline)
+                } else {
+                    Some(TriviaKind::Whitespace)
+                }
             }
-            '\n' => {
-                i += 1;
-                line += 1;
-                column = 1;
+            '/' if self.cursor.peek_nth(1) == Some('/') && !self.at_doc_comment() => {
+                self.cursor.eat_while(|c| c != '\n' && c != '\r');
+                Some(TriviaKind::LineComment)
             }
+            '/' if self.cursor.peek_nth(1) == Some('*') && !self.at_doc_comment() => {
+                let start: Cursor = self.cursor.clone();
+                self.cursor.bump();
+                self.cursor.bump();
+                self.skip_block_comment(&start);
+                Some(TriviaKind::BlockComment)
+            }
+            _ => None,
+        }
+    }
+
+    // Consumes one lexeme starting with `c`. Returns `None` on errors.
+    fn lex_token(&mut self, c: char, start: &Cursor) -> Option<Token> {
+        self.cursor.bump();
+
+        let token_type: TokenType = match c {
             '+' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::AddByValue,
-                        value: "+=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '+' {
-                    tokens.push(Token {
-                        token_type: TokenType::Increment,
-                        value: "++".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::AddByValue
+                } else if self.cursor.eat('+') {
+                    TokenType::Increment
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Plus,
-                        value: "+".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Plus
                 }
             }
             '-' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::SubtractByValue,
-                        value: "-=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '-' {
-                    tokens.push(Token {
-                        token_type: TokenType::Decrement,
-                        value: "--".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '>' {
-                    tokens.push(Token {
-                        token_type: TokenType::Arrow,
-                        value: "->".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::SubtractByValue
+                } else if self.cursor.eat('-') {
+                    TokenType::Decrement
+                } else if self.cursor.eat('>') {
+                    TokenType::Arrow
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Minus,
-                        value: "-".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Minus
                 }
             }
             '*' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::MultiplyByValue,
-                        value: "*=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::MultiplyByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Multiply,
-                        value: "*".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Multiply
                 }
             }
             '/' => {
-                if source.chars().nth(i + 1).unwrap() == '/' {
-                    while source.chars().nth(i).unwrap() != '\n' {
-                        i += 1;
-                    }
-                } else if source.chars().nth(i + 1).unwrap() == '*' {
-                    i += 2;
-                    while source.chars().nth(i).unwrap() != '*' && source.chars().nth(i + 1).unwrap() != '/' {
-                        i += 1;
-                    }
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::DivideByValue,
-                        value: "/=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if matches!(self.cursor.peek(), Some('/' | '*')) {
+                    return self.lex_doc_comment(start);
+                } else if self.cursor.eat('=') {
+                    TokenType::DivideByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Divide,
-                        value: "/".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Divide
                 }
             }
             '%' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::ModuloByValue,
-                        value: "%=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::ModuloByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Modulo,
-                        value: "%".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Modulo
                 }
             }
             '=' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::Equal,
-                        value: "==".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '>' {
-                    tokens.push(Token {
-                        token_type: TokenType::FatArrow,
-                        value: "=>".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::Equal
+                } else if self.cursor.eat('>') {
+                    TokenType::FatArrow
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::Assignment,
-                        value: "=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::Assignment
                 }
             }
             '!' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::NotEqual,
-                        value: "!=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::NotEqual
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::LogicalNot,
-                        value: "!".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::LogicalNot
                 }
             }
             '>' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::GreaterThanOrEqual,
-                        value: ">=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::GreaterThanOrEqual
+                } else if self.cursor.eat('>') {
+                    if self.cursor.eat('>') {
+                        if self.cursor.eat('=') {
+                            TokenType::BitwiseUnsignedRightShiftByValue
+                        } else {
+                            TokenType::BitwiseUnsignedRightShift
+                        }
+                    } else if self.cursor.eat('=') {
+                        TokenType::BitwiseRightShiftByValue
+                    } else {
+                        TokenType::BitwiseRightShift
+                    }
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::GreaterThan,
-                        value: ">".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::GreaterThan
                 }
             }
             '<' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::LessThanOrEqual,
-                        value: "<=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '<' {
-                    if source.chars().nth(i + 2).unwrap() == '=' {
-                        tokens.push(Token {
-                            token_type: TokenType::BitwiseLeftShiftByValue,
-                            value: "<<=".to_string(),
-                            line,
-                            column,
-                        });
-                        i += 3;
+                if self.cursor.eat('=') {
+                    TokenType::LessThanOrEqual
+                } else if self.cursor.eat('<') {
+                    if self.cursor.eat('=') {
+                        TokenType::BitwiseLeftShiftByValue
                     } else {
-                        tokens.push(Token {
-                            token_type: TokenType::BitwiseLeftShift,
-                            value: "<<".to_string(),
-                            line,
-                            column,
-                        });
-                        i += 2;
+                        TokenType::BitwiseLeftShift
                     }
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::LessThan,
-                        value: "<".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::LessThan
                 }
             }
             '&' => {
-                if source.chars().nth(i + 1).unwrap() == '&' {
-                    tokens.push(Token {
-                        token_type: TokenType::LogicalAnd,
-                        value: "&&".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseAndByValue,
-                        value: "&=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('&') {
+                    TokenType::LogicalAnd
+                } else if self.cursor.eat('=') {
+                    TokenType::BitwiseAndByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseAnd,
-                        value: "&".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::BitwiseAnd
                 }
             }
             '|' => {
-                if source.chars().nth(i + 1).unwrap() == '|' {
-                    tokens.push(Token {
-                        token_type: TokenType::LogicalOr,
-                        value: "||".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
-                } else if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseOrByValue,
-                        value: "|=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('|') {
+                    TokenType::LogicalOr
+                } else if self.cursor.eat('=') {
+                    TokenType::BitwiseOrByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseOr,
-                        value: "|".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::BitwiseOr
                 }
             }
             '^' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseXorByValue,
-                        value: "^=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::BitwiseXorByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseXor,
-                        value: "^".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
+                    TokenType::BitwiseXor
                 }
             }
             '~' => {
-                if source.chars().nth(i + 1).unwrap() == '=' {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseNotByValue,
-                        value: "~=".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 2;
+                if self.cursor.eat('=') {
+                    TokenType::BitwiseNotByValue
                 } else {
-                    tokens.push(Token {
-                        token_type: TokenType::BitwiseNot,
-                        value: "~".to_string(),
-                        line,
-                        column,
-                    });
-                    i += 1;
-                }
-            }
-            '(' => {
-                tokens.push(Token {
-                    token_type: TokenType::OpenParenthesis,
-                    value: "(".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            ')' => {
-                tokens.push(Token {
-                    token_type: TokenType::CloseParenthesis,
-                    value: ")".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            '{' => {
-                tokens.push(Token {
-                    token_type: TokenType::OpenBrace,
-                    value: "{".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            '}' => {
-                tokens.push(Token {
-                    token_type: TokenType::CloseBrace,
-                    value: "}".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            '[' => {
-                tokens.push(Token {
-                    token_type: TokenType::OpenBracket,
-                    value: "[".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            ']' => {
-                tokens.push(Token {
-                    token_type: TokenType::CloseBracket,
-                    value: "]".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            ',' => {
-                tokens.push(Token {
-                    token_type: TokenType::Comma,
-                    value: ",".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            '.' => {
-                tokens.push(Token {
-                    token_type: TokenType::Dot,
-                    value: ".".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            ';' => {
-                tokens.push(Token {
-                    token_type: TokenType::Semicolon,
-                    value: ";".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
+                    TokenType::BitwiseNot
+                }
             }
+            '(' => TokenType::OpenParenthesis,
+            ')' => TokenType::CloseParenthesis,
+            '{' => TokenType::OpenBrace,
+            '}' => TokenType::CloseBrace,
+            '[' => TokenType::OpenBracket,
+            ']' => TokenType::CloseBracket,
+            ',' => TokenType::Comma,
+            '.' => TokenType::Dot,
+            ';' => TokenType::Semicolon,
             ':' => {
-                tokens.push(Token {
-                    token_type: TokenType::Colon,
-                    value: ":".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
+                if self.cursor.eat(':') {
+                    TokenType::DoubleColon
+                } else {
+                    TokenType::Colon
+                }
             }
             '?' => {
-                tokens.push(Token {
-                    token_type: TokenType::QuestionMark,
-                    value: "?".to_string(),
-                    line,
-                    column,
-                });
-                i += 1;
-            }
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let mut value = String::new();
-                value.push(source.chars().nth(i).unwrap());
-                i += 1;
-                while source.chars().nth(i).unwrap().is_alphanumeric() || source.chars().nth(i).unwrap() == '_' {
-                    value.push(source.chars().nth(i).unwrap());
-                    i += 1;
-                }
-
-                let token_type = match value.as_str() {
+                if self.cursor.eat('.') {
+                    TokenType::QuestionDot
+                } else if self.cursor.eat('?') {
+                    TokenType::NullCoalescing
+                } else {
+                    TokenType::QuestionMark
+                }
+            }
+            'r' if self.cursor.peek() == Some('"') => {
+                self.cursor.bump();
+                return self.lex_string(start, true);
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                self.cursor.eat_while(|c| c.is_alphanumeric() || c == '_');
+
+                match &start.source[start.position..self.cursor.position] {
                     "void" => TokenType::Void,
                     "string" => TokenType::String,
                     "char" => TokenType::Char,
@@ -565,90 +786,508 @@ pub fn lex(source: String) -> Vec<Token> {
                     "boolean" => TokenType::Boolean,
                     "const" => TokenType::Const,
                     "import" => TokenType::Import,
+                    "export" => TokenType::Export,
                     "func" => TokenType::Func,
                     "lambda" => TokenType::Lambda,
                     "array" => TokenType::Array,
                     "map" => TokenType::Map,
                     "ptr" => TokenType::Pointer,
                     "return" => TokenType::Return,
+                    "if" => TokenType::If,
+                    "else" => TokenType::Else,
+                    "while" => TokenType::While,
+                    "for" => TokenType::For,
+                    "continue" => TokenType::Continue,
+                    "break" => TokenType::Break,
+                    "new" => TokenType::New,
+                    "throw" => TokenType::Throw,
+                    "override" => TokenType::Override,
+                    "this" => TokenType::This,
+                    "class" => TokenType::Class,
+                    "enum" => TokenType::Enum,
+                    "namespace" => TokenType::Namespace,
                     "true" | "false" => TokenType::BooleanLiteral,
+                    "null" => TokenType::NullLiteral,
                     _ => TokenType::IdentifierLiteral,
-                };
-
-                tokens.push(Token {
-                    token_type,
-                    value,
-                    line,
-                    column,
-                });
-            }
-            '0'..='9' => {
-                let mut value = String::new();
-                value.push(source.chars().nth(i).unwrap());
-                i += 1;
-                while source.chars().nth(i).unwrap().is_numeric() || source.chars().nth(i).unwrap() == '.' {
-                    value.push(source.chars().nth(i).unwrap());
-                    i += 1;
-                }
-
-                let token_type = match value.contains('.') {
-                    true => TokenType::FloatLiteral,
-                    false => TokenType::NumberLiteral,
-                };
-
-                tokens.push(Token {
-                    token_type,
-                    value,
-                    line,
-                    column,
-                });                
-            }
-            '"' => {
-                let mut value = String::new();
-                i += 1;
-                while source.chars().nth(i).unwrap() != '"' {
-                    value.push(source.chars().nth(i).unwrap());
-                    i += 1;
-                }
-                i += 1;
-
-                tokens.push(Token {
-                    token_type: TokenType::StringLiteral,
-                    value,
-                    line,
-                    column,
-                });
-            }
-            '\'' => {
-                let mut value = String::new();
-                i += 1;
-                while source.chars().nth(i).unwrap() != '\'' {
-                    value.push(source.chars().nth(i).unwrap());
-                    i += 1;
-                }
-                i += 1;
-
-                if value.len() != 1 {
-                    panic!("Invalid char literal");
-                }
-
-                tokens.push(Token {
-                    token_type: TokenType::CharLiteral,
-                    value,
-                    line,
-                    column,
-                });
-            }
-            _ => panic!("Unexpected character: {}", source.chars().nth(i).unwrap()),
-        }
-    }
-
-    tokens.push(Token {
-        token_type: TokenType::EndOfFile,
-        value: "".to_string(),
-        line,
-        column,
-    });
-
-    tokens
-}
\ No newline at end of file
+                }
+            }
+            '0'..='9' => return self.lex_number(start),
+            '"' => return self.lex_string(start, false),
+            '\'' => return self.lex_char(start),
+            '`' => return self.lex_template(start),
+            _ => {
+                self.errors.push(LexError::UnexpectedCharacter { character: c, span: self.cursor.span_from(start) });
+                return None;
+            }
+        };
+
+        let value: String = start.source[start.position..self.cursor.position].to_string();
+        Some(self.token(token_type, value, start))
+    }
+
+    // Scans everything that could belong to a number literal, so malformed
+    // literals such as `1.2.3` or `0b102` are reported as a whole.
+    fn lex_number(&mut self, start: &Cursor) -> Option<Token> {
+        let prefixed: bool = start.peek() == Some('0') && matches!(self.cursor.peek(), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+        let mut previous: char = '0';
+        loop {
+            match self.cursor.peek() {
+                Some(c) if c.is_alphanumeric() || c == '_' => {}
+                Some('.') if !prefixed && self.cursor.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) => {}
+                Some('+' | '-') if !prefixed && matches!(previous, 'e' | 'E') && self.cursor.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) => {}
+                _ => break,
+            }
+            previous = self.cursor.bump().unwrap();
+        }
+
+        let text: &str = &start.source[start.position..self.cursor.position];
+        let value: String = text.replace('_', "");
+        match split_number_literal(&value).filter(|(radix, _, _)| separates_digits(text, *radix)) {
+            Some((_, _, kind)) if kind.is_float() => Some(self.token(TokenType::FloatLiteral, value, start)),
+            Some(_) => Some(self.token(TokenType::NumberLiteral, value, start)),
+            None => {
+                self.errors.push(LexError::InvalidNumber { value: text.to_string(), span: self.cursor.span_from(start) });
+                None
+            }
+        }
+    }
+
+    // Lexes a string literal after its opening quote. `"""` opens a
+    // multi-line string, and raw strings (`r"..."`) keep backslashes as-is.
+    fn lex_string(&mut self, start: &Cursor, raw: bool) -> Option<Token> {
+        let triple: bool = self.cursor.peek() == Some('"') && self.cursor.peek_nth(1) == Some('"');
+        if triple {
+            self.cursor.bump();
+            self.cursor.bump();
+        }
+
+        let mut value: String = String::new();
+        let mut valid: bool = true;
+        loop {
+            match self.cursor.peek() {
+                None => {
+                    self.errors.push(LexError::UnterminatedString { span: self.cursor.span_from(start) });
+                    return None;
+                }
+                // Only triple-quoted strings can span lines.
+                Some('\n') if !triple => {
+                    self.errors.push(LexError::UnterminatedString { span: self.cursor.span_from(start) });
+                    self.skip_to_stray_quote(raw);
+                    return None;
+                }
+                Some('"') if !triple => {
+                    self.cursor.bump();
+                    break;
+                }
+                Some('"') if self.cursor.peek_nth(1) == Some('"') && self.cursor.peek_nth(2) == Some('"') => {
+                    self.cursor.bump();
+                    self.cursor.bump();
+                    self.cursor.bump();
+                    break;
+                }
+                Some('\\') if !raw => match self.lex_escape() {
+                    Some(c) => value.push(c),
+                    None => valid = false,
+                },
+                Some(c) => {
+                    self.cursor.bump();
+                    value.push(c);
+                }
+            }
+        }
+
+        if !valid {
+            return None;
+        }
+        Some(self.token(TokenType::StringLiteral, value, start))
+    }
+
+    // Recovers from a plain string broken across lines. When the next line
+    // has an odd number of quotes, its first quote is taken to close the
+    // string, so `"a` followed by `b";` is one error rather than two. Otherwise
+    // the string is taken to be missing its closing quote and lexing goes on
+    // at the newline.
+    fn skip_to_stray_quote(&mut self, raw: bool) {
+        let next_line: &str = self.cursor.source[self.cursor.position + 1..].split('\n').next().unwrap_or("");
+        let mut quotes: Vec<usize> = Vec::new();
+        let mut escaped: bool = false;
+        for (offset, c) in next_line.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' if !raw => escaped = true,
+                '"' => quotes.push(offset),
+                _ => {}
+            }
+        }
+        if quotes.len() % 2 == 1 {
+            let end: usize = self.cursor.position + 1 + quotes[0];
+            while self.cursor.position <= end {
+                self.cursor.bump();
+            }
+        }
+    }
+
+    // Lexes a template literal after its opening backtick, lexing each
+    // `${...}` up to its matching brace into a separate token list.
+    fn lex_template(&mut self, start: &Cursor) -> Option<Token> {
+        let mut parts: Vec<TemplatePart> = Vec::new();
+        let mut text: String = String::new();
+        let mut text_start: Cursor = self.cursor.clone();
+        let mut valid: bool = true;
+        loop {
+            match self.cursor.peek() {
+                None => {
+                    self.errors.push(LexError::UnterminatedTemplate { span: self.cursor.span_from(start) });
+                    return None;
+                }
+                Some('`') => break,
+                Some('\\') => match self.lex_escape() {
+                    Some(c) => text.push(c),
+                    None => valid = false,
+                },
+                Some('$') if self.cursor.peek_nth(1) == Some('{') => {
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text), self.cursor.span_from(&text_start)));
+                    }
+                    self.cursor.bump();
+                    self.cursor.bump();
+                    parts.push(TemplatePart::Expression(self.lex_template_expression(start)?));
+                    text_start = self.cursor.clone();
+                }
+                Some(c) => {
+                    self.cursor.bump();
+                    text.push(c);
+                }
+            }
+        }
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text, self.cursor.span_from(&text_start)));
+        }
+        self.cursor.bump();
+
+        if !valid {
+            return None;
+        }
+        let mut token: Token = self.token(TokenType::TemplateLiteral, "".to_string(), start);
+        token.parts = parts;
+        Some(token)
+    }
+
+    fn lex_template_expression(&mut self, start: &Cursor) -> Option<Vec<Token>> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut depth: usize = 0;
+        loop {
+            let Some(c) = self.cursor.peek() else {
+                self.errors.push(LexError::UnterminatedTemplate { span: self.cursor.span_from(start) });
+                return None;
+            };
+            let token_start: Cursor = self.cursor.clone();
+            if c == '}' && depth == 0 {
+                tokens.push(self.token(TokenType::EndOfFile, "".to_string(), &token_start));
+                self.cursor.bump();
+                return Some(tokens);
+            }
+            if self.lex_trivia(c).is_some() {
+                continue;
+            }
+            if let Some(token) = self.lex_token(c, &token_start) {
+                match token.token_type {
+                    TokenType::OpenBrace => depth += 1,
+                    TokenType::CloseBrace => depth -= 1,
+                    // An embedded expression declares nothing to document.
+                    TokenType::DocComment => continue,
+                    _ => {}
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    fn lex_char(&mut self, start: &Cursor) -> Option<Token> {
+        let mut value: String = String::new();
+        let mut valid: bool = true;
+        loop {
+            match self.cursor.peek() {
+                None | Some('\n') => {
+                    self.errors.push(LexError::UnterminatedChar { span: self.cursor.span_from(start) });
+                    return None;
+                }
+                Some('\'') => {
+                    self.cursor.bump();
+                    break;
+                }
+                Some('\\') => match self.lex_escape() {
+                    Some(c) => value.push(c),
+                    None => valid = false,
+                },
+                Some(c) => {
+                    self.cursor.bump();
+                    value.push(c);
+                }
+            }
+        }
+
+        if !valid {
+            return None;
+        }
+        if value.chars().count() != 1 {
+            self.errors.push(LexError::InvalidChar { value, span: self.cursor.span_from(start) });
+            return None;
+        }
+        Some(self.token(TokenType::CharLiteral, value, start))
+    }
+
+    // Decodes the escape sequence under the cursor, reporting it at the
+    // column of its backslash when it isn't valid.
+    fn lex_escape(&mut self) -> Option<char> {
+        let escape: Cursor = self.cursor.clone();
+        self.cursor.bump();
+
+        let decoded: Option<char> = match self.cursor.bump() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('r') => Some('\r'),
+            Some('0') => Some('\0'),
+            Some('\\') => Some('\\'),
+            Some('"') => Some('"'),
+            Some('\'') => Some('\''),
+            Some('`') => Some('`'),
+            Some('$') => Some('$'),
+            Some('u') if self.cursor.eat('{') => {
+                let code: Option<u32> = u32::from_str_radix(self.cursor.eat_while(|c| c.is_ascii_hexdigit()), 16).ok();
+                if self.cursor.eat('}') {
+                    code.and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        if decoded.is_none() {
+            self.errors.push(LexError::InvalidEscape {
+                sequence: escape.source[escape.position..self.cursor.position].to_string(),
+                span: self.cursor.span_from(&escape),
+            });
+        }
+        decoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The errors of lexing `source`, as (message, line, column, the text
+    // they cover).
+    fn lex_errors(source: &str) -> Vec<(String, usize, usize, &str)> {
+        lex(source.to_string())
+            .unwrap_err()
+            .into_iter()
+            .map(|error| {
+                let span = error.span();
+                (error.to_string(), span.line, span.column, &source[span.start_byte..span.end_byte])
+            })
+            .collect()
+    }
+
+    fn assert_round_trip(source: &str) {
+        let tokens = lex_lossless(source.to_string()).unwrap();
+        assert_eq!(to_source(&tokens), source);
+    }
+
+    #[test]
+    fn tokens_know_their_line_column_and_bytes() {
+        let source = "s: string = \"é😀\";\n/* ä\n b */ x /* c */ = \"\"\"l1\nl2\"\"\" + 'ü' + é;\n";
+        let spans: Vec<(&str, usize, usize, usize)> = lex(source.to_string())
+            .unwrap()
+            .into_iter()
+            .map(|token| (&source[token.span.start_byte..token.span.end_byte], token.span.line, token.span.column, token.span.start_byte))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("s", 1, 1, 0),
+                (":", 1, 2, 1),
+                ("string", 1, 4, 3),
+                ("=", 1, 11, 10),
+                ("\"é😀\"", 1, 13, 12),
+                (";", 1, 17, 20),
+                ("x", 3, 7, 34),
+                ("=", 3, 17, 44),
+                ("\"\"\"l1\nl2\"\"\"", 3, 19, 46),
+                ("+", 4, 7, 58),
+                ("'ü'", 4, 9, 60),
+                ("+", 4, 13, 65),
+                ("é", 4, 15, 67),
+                (";", 4, 16, 69),
+                ("", 5, 1, 71),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless_lexing_rebuilds_the_example_programs() {
+        assert_round_trip(include_str!("../tests/var_decl.wly"));
+        assert_round_trip(include_str!("../tests/import_test.wly"));
+        assert_round_trip(include_str!("../tests/cursed_maps.wly"));
+        assert_round_trip(include_str!("../tests/everything.wly"));
+    }
+
+    #[test]
+    fn lossless_lexing_keeps_comments_and_literal_text() {
+        assert_round_trip("x: int = 0x_ff; // hex\r\n\t/* a /* nested */ comment */ y:double=1e3;\n");
+        assert_round_trip("/// doc\ns: string = \"a\\tb\" + r\"\\n\" + \"\"\"multi\nline\"\"\";\n");
+        assert_round_trip("t: string = `x = ${ x + 1 } and ${ `${y}` }`;   ");
+        assert_round_trip("c: char = '\\u{1F600}';");
+        assert_round_trip("");
+    }
+
+    #[test]
+    fn lexing_reports_every_error_in_one_pass() {
+        let source = "a: int = 1 @ 2;\nb: char = 'xy';\nc: string = \"\\q\";\nd: string = \"open\ne: int = #;\n";
+        assert_eq!(
+            lex_errors(source),
+            vec![
+                ("unexpected character '@'".to_string(), 1, 12, "@"),
+                ("char literal 'xy' must contain exactly one character".to_string(), 2, 11, "'xy'"),
+                ("invalid escape sequence '\\q'".to_string(), 3, 14, "\\q"),
+                ("unterminated string literal".to_string(), 4, 13, "\"open"),
+                ("unexpected character '#'".to_string(), 5, 10, "#"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexing_decodes_escaped_raw_and_triple_quoted_strings() {
+        let source = "\"a\\tb\\\"c\\u{e9}\" r\"a\\tb\" \"\"\"one\ntwo \"quoted\" end\"\"\" r\"\"\"\\n\nx\"\"\"";
+        let values: Vec<String> = lex(source.to_string())
+            .unwrap()
+            .into_iter()
+            .filter(|token| token.token_type == TokenType::StringLiteral)
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(values, vec!["a\tb\"c\u{e9}", "a\\tb", "one\ntwo \"quoted\" end", "\\n\nx"]);
+    }
+
+    #[test]
+    fn lexing_reports_each_unterminated_string_once() {
+        // A string broken across lines, closed on the next line.
+        assert_eq!(
+            lex_errors("s: string = \"abc\n\";\nt: int = 1;\n"),
+            vec![("unterminated string literal".to_string(), 1, 13, "\"abc")]
+        );
+        assert_eq!(
+            lex_errors("s: string = \"a\nb \\\" c\";\n"),
+            vec![("unterminated string literal".to_string(), 1, 13, "\"a")]
+        );
+        // A string missing its closing quote.
+        assert_eq!(
+            lex_errors("s: string = \"abc;\nt: string = \"def\";\n"),
+            vec![("unterminated string literal".to_string(), 1, 13, "\"abc;")]
+        );
+        assert_eq!(
+            lex_errors("s: string = \"\"\"abc\n\""),
+            vec![("unterminated string literal".to_string(), 1, 13, "\"\"\"abc\n\"")]
+        );
+    }
+
+    #[test]
+    fn lexing_reports_unterminated_comments_and_templates() {
+        assert_eq!(
+            lex_errors("x /* a /* b */"),
+            vec![("unterminated block comment".to_string(), 1, 3, "/* a /* b */")]
+        );
+        assert_eq!(
+            lex_errors("`a ${b"),
+            vec![("unterminated template literal".to_string(), 1, 1, "`a ${b")]
+        );
+    }
+
+    #[test]
+    fn lexing_splits_number_literals_and_checks_separators() {
+        let literals: Vec<(TokenType, String)> = lex("1_000 0x_FF_ff 0b1010u8 7l 1.5e-3 2f 3.0d".to_string())
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.token_type, token.value))
+            .collect();
+        assert_eq!(
+            literals,
+            vec![
+                (TokenType::NumberLiteral, "1000".to_string()),
+                (TokenType::NumberLiteral, "0xFFff".to_string()),
+                (TokenType::NumberLiteral, "0b1010u8".to_string()),
+                (TokenType::NumberLiteral, "7l".to_string()),
+                (TokenType::FloatLiteral, "1.5e-3".to_string()),
+                (TokenType::FloatLiteral, "2f".to_string()),
+                (TokenType::FloatLiteral, "3.0d".to_string()),
+                (TokenType::EndOfFile, String::new()),
+            ]
+        );
+        assert_eq!(split_number_literal("0b1010u8"), Some((2, "1010", NumberKind::Byte)));
+        assert_eq!(split_number_literal("1.5e-3"), Some((10, "1.5e-3", NumberKind::Double)));
+        assert_eq!(split_number_literal("0x1.5"), None);
+        assert_eq!(
+            lex_errors("a: int = 1_;
+b: int = 1__0;
+c: int = 1_u8 + 1_.5 + 0x__1;
+"),
+            vec![
+                ("invalid number literal '1_'".to_string(), 1, 10, "1_"),
+                ("invalid number literal '1__0'".to_string(), 2, 10, "1__0"),
+                ("invalid number literal '1_u8'".to_string(), 3, 10, "1_u8"),
+                ("invalid number literal '1_.5'".to_string(), 3, 17, "1_.5"),
+                ("invalid number literal '0x__1'".to_string(), 3, 24, "0x__1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn templates_keep_their_text_and_expression_tokens() {
+        let source = "`a\\${b ${ x + 1 }c${ { \"k\": `${y}` }[\"k\"] }`";
+        let tokens = lex(source.to_string()).unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::TemplateLiteral);
+        let parts: Vec<String> = tokens[0]
+            .parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text, span) => format!("{:?} at {}", text, &source[span.start_byte..span.end_byte]),
+                TemplatePart::Expression(tokens) => tokens.iter().map(|token| format!("{:?}", token.token_type)).collect::<Vec<String>>().join(" "),
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                "\"a${b \" at a\\${b ",
+                "IdentifierLiteral Plus NumberLiteral EndOfFile",
+                "\"c\" at c",
+                "OpenBrace StringLiteral Colon TemplateLiteral CloseBrace OpenBracket StringLiteral CloseBracket EndOfFile",
+            ]
+        );
+    }
+
+    #[test]
+    fn doc_comments_are_tokens_only_before_declarations() {
+        let source = "/// doc\nx: int = /** five */ 5;\nf: func<int> = (\n    /// a\n    a: int) => {\n    /** y */ y: int = { /// k\n \"k\": a };\n}\n";
+        let docs: Vec<String> = lex(source.to_string())
+            .unwrap()
+            .into_iter()
+            .filter(|token| token.token_type == TokenType::DocComment)
+            .map(|token| token.value)
+            .collect();
+        assert_eq!(docs, vec!["doc", "y"]);
+        assert_round_trip(source);
+    }
+
+    #[test]
+    fn plain_lexing_drops_trivia() {
+        for source in ["a /* b */ c // d\n", "x: int = /** five */ 5;\n"] {
+            let tokens = lex(source.to_string()).unwrap();
+            assert!(tokens.iter().all(|token| token.leading_trivia.is_empty() && token.trailing_trivia.is_empty()));
+        }
+        assert_eq!(to_source(&lex("a /* b */ c // d\n".to_string()).unwrap()), "ac");
+    }
+}
@@ -1,13 +1,10 @@
-mod lexer;
-mod parser;
-mod executor;
-
-use crate::lexer::*;
-use crate::parser::*;
-use crate::executor::*;
+use wally::lexer::*;
+use wally::parser::*;
+use wally::executor::*;
 use std::fs::File;
 use std::io::prelude::*;
 use std::env::args;
+use std::process::exit;
 
 fn main() {
     let args: Vec<String> = args().collect();
@@ -16,11 +13,62 @@ fn main() {
     let mut contents: String = String::new();
     file.read_to_string(&mut contents)
         .expect("Something went wrong reading the file");
-    let tokens: Vec<Token> = lex(contents);
+    let tokens: Vec<Token> = match lex(contents.clone()) {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            for error in &errors {
+                report(filename, &contents, error.span(), error);
+            }
+            exit(1);
+        }
+    };
     for token in &tokens {
         println!("{:?}", token);
     }
-    let program: Program = parse(&tokens);
+    let program: Program = match parse(&tokens) {
+        Ok(program) => program,
+        Err(errors) => {
+            for error in &errors {
+                report(filename, &contents, error.span, error);
+            }
+            exit(1);
+        }
+    };
     println!("{:#?}", program);
-    execute(program);
+    if let Err(error) = execute(program) {
+        // Errors in imported files are reported in those files.
+        let (filename, contents) = match &error.file {
+            Some(file) => (file.clone(), std::fs::read_to_string(file).unwrap_or_default()),
+            None => (filename.clone(), contents),
+        };
+        match error.span {
+            Some(span) => report(&filename, &contents, span, &error),
+            None => eprintln!("{}: error: {}", filename, error),
+        }
+        exit(1);
+    }
+}
+
+// Prints an error with the line it is on, underlining the part of the line
+// the span covers.
+fn report(filename: &str, source: &str, span: Span, error: &dyn std::fmt::Display) {
+    eprintln!("{}:{}:{}: error: {}", filename, span.line, span.column, error);
+    let Some(line) = source.lines().nth(span.line.saturating_sub(1)) else {
+        return;
+    };
+    let start = span.start_byte.min(source.len());
+    let end = match source[start..].find('\n') {
+        Some(length) => span.end_byte.min(start + length),
+        None => span.end_byte.min(source.len()),
+    };
+    let width = source.get(start..end).map_or(0, |text| text.chars().count()).max(1);
+    // Tabs are kept so the underline lines up with the text above it.
+    let indent: String = line
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let gutter = " ".repeat(span.line.to_string().len());
+    eprintln!("{} | {}", span.line, line);
+    eprintln!("{} | {}{}", gutter, indent, "^".repeat(width));
 }
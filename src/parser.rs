@@ -1,4 +1,4 @@
-use crate::lexer::{Token, TokenType};
+use crate::lexer::{split_number_literal, NumberKind, Span, TemplatePart, Token, TokenType};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
@@ -9,42 +9,239 @@ pub struct Program {
 pub enum Statement {
     VariableDeclaration(VariableDeclaration),
     FunctionDeclaration(FunctionDeclaration),
-    FunctionCall(FunctionCall),
+    ClassDeclaration(ClassDeclaration),
+    EnumDeclaration(EnumDeclaration),
+    Namespace(Namespace),
     Import(Import),
     Return(Return),
+    Block(Block),
+    If(If),
+    While(While),
+    For(For),
+    ForIn(ForIn),
+    Break(Span),
+    Continue(Span),
+    Assign(Assign),
+    Expression(Expression),
+}
+
+impl Statement {
+    // The source the statement was parsed from, without its doc comment.
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::VariableDeclaration(variable) => variable.span,
+            Statement::FunctionDeclaration(function) => function.span,
+            Statement::ClassDeclaration(class) => class.span,
+            Statement::EnumDeclaration(declaration) => declaration.span,
+            Statement::Namespace(namespace) => namespace.span,
+            Statement::Import(import) => import.span,
+            Statement::Return(ret) => ret.span,
+            Statement::Block(block) => block.span,
+            Statement::If(if_) => if_.span,
+            Statement::While(while_) => while_.span,
+            Statement::For(for_) => for_.span,
+            Statement::ForIn(for_in) => for_in.span,
+            Statement::Break(span) | Statement::Continue(span) => *span,
+            Statement::Assign(assign) => assign.span,
+            Statement::Expression(expression) => expression.span(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct VariableDeclaration {
     pub name: String,
     pub type_: Type,
+    // Where the type annotation was written, which type errors point at.
+    pub type_span: Span,
     pub value: Expression,
     pub immutable: bool,
+    pub doc: Option<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionDeclaration {
     pub name: String,
-    pub parameters: Vec<(String, Type, bool, Expression)>,
+    // `identity<T>: func<T> = (x: T) => ...` declares `T`.
+    pub type_parameters: Vec<String>,
+    pub parameters: Vec<Param>,
     pub return_type: Type,
-    pub body: Vec<Statement>,
+    // The span of `func<R>`.
+    pub return_type_span: Span,
+    pub body: Block,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+// A parameter of a function, lambda, class or enum. `name?: T` and
+// `name: T = value` are optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub type_: Type,
+    pub type_span: Span,
+    pub optional: bool,
+    pub default_value: Option<Expression>,
+    pub span: Span,
+}
+
+// The constructor parameters become fields of every instance, next to the
+// fields declared in the body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDeclaration {
+    pub name: String,
+    pub type_parameters: Vec<String>,
+    pub parameters: Vec<Param>,
+    pub fields: Vec<VariableDeclaration>,
+    pub methods: Vec<FunctionDeclaration>,
+    // Names of the members marked with `override`.
+    pub overrides: Vec<String>,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+// Every class implicitly extends `Object`, which provides these methods.
+pub const OBJECT_METHODS: &[&str] = &["toString", "equals"];
+
+// `E: enum => A, B;` has no parameters. The Java-style
+// `E: enum = (p: T) => { A(x), B(y) }` passes arguments to every variant,
+// which become its fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDeclaration {
+    pub name: String,
+    pub parameters: Vec<Param>,
+    pub variants: Vec<EnumVariant>,
+    pub doc: Option<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct FunctionCall {
+pub struct EnumVariant {
     pub name: String,
     pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Namespace {
+    pub name: String,
+    pub body: Block,
+    pub doc: Option<String>,
+    pub span: Span,
+}
+
+// A name qualified by the namespaces it lives in, such as `Lexer::Token`.
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub segments: Vec<String>,
+    pub span: Span,
+}
+
+// Paths are equal wherever they were written, so types naming the same
+// class compare equal.
+impl PartialEq for Path {
+    fn eq(&self, other: &Path) -> bool {
+        self.segments == other.segments
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.segments.join("::"))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Import {
     pub path: String,
-    pub methods: Expression,
+    pub spec: ImportSpec,
+    // `export const ...` makes the imported names available to files that
+    // import this one.
+    pub exported: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportSpec {
+    // `const io = import("io");`
+    Alias(Identifier),
+    // `const { print, println as say } = import("io");`, as (name, rename).
+    Named(Vec<(Identifier, Option<Identifier>)>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Return {
+    // `None` for a bare `return;`.
+    pub value: Option<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub span: Span,
+}
+
+// `else if` chains are stored as an `else_body` holding another `If`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct If {
+    pub condition: Expression,
+    pub body: Block,
+    pub else_body: Option<Box<Statement>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct While {
+    pub condition: Expression,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct For {
+    pub init: Option<Box<Statement>>,
+    pub condition: Option<Expression>,
+    pub step: Option<Box<Statement>>,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForIn {
+    pub name: String,
+    pub type_: Option<Type>,
+    pub type_span: Option<Span>,
+    pub iterable: Expression,
+    pub body: Block,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assign {
+    pub target: Expression,
+    pub operator: AssignOperator,
+    pub operator_span: Span,
     pub value: Expression,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssignOperator {
+    Assign,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseNot,
+    LeftShift,
+    RightShift,
+    UnsignedRightShift,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -53,46 +250,247 @@ pub enum Expression {
     MapLiteral(MapLiteral),
     NumberLiteral(NumberLiteral),
     StringLiteral(StringLiteral),
+    Template(Template),
     CharacterLiteral(CharacterLiteral),
     BooleanLiteral(BooleanLiteral),
+    Null(Span),
     Identifier(Identifier),
+    Path(Path),
+    Binary(Binary),
+    Unary(Unary),
+    Postfix(Postfix),
+    Grouping(Grouping),
+    Call(Call),
+    Member(Member),
+    Index(Index),
+    Lambda(Lambda),
+    New(New),
+    Module(Module),
+    This(Span),
+}
 
-    None,
+impl Expression {
+    // The source the expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::ArrayLiteral(array) => array.span,
+            Expression::MapLiteral(map) => map.span,
+            Expression::NumberLiteral(number) => number.span,
+            Expression::StringLiteral(string) => string.span,
+            Expression::Template(template) => template.span,
+            Expression::CharacterLiteral(character) => character.span,
+            Expression::BooleanLiteral(boolean) => boolean.span,
+            Expression::Null(span) | Expression::This(span) => *span,
+            Expression::Identifier(identifier) => identifier.span,
+            Expression::Path(path) => path.span,
+            Expression::Binary(binary) => binary.span,
+            Expression::Unary(unary) => unary.span,
+            Expression::Postfix(postfix) => postfix.span,
+            Expression::Grouping(grouping) => grouping.span,
+            Expression::Call(call) => call.span,
+            Expression::Member(member) => member.span,
+            Expression::Index(index) => index.span,
+            Expression::Lambda(lambda) => lambda.span,
+            Expression::New(new) => new.span,
+            Expression::Module(module) => module.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ArrayLiteral {
     pub elements: Vec<Expression>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MapLiteral {
     pub elements: Vec<(Expression, Expression)>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct NumberLiteral {
-    pub value: f64,
+    pub value: Number,
+    pub kind: NumberKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(u128),
+    Float(f64),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct StringLiteral {
     pub value: String,
+    pub span: Span,
+}
+
+// An interpolated string. At runtime its parts are converted to strings and
+// concatenated in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub parts: Vec<TemplateElement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateElement {
+    Text(String, Span),
+    Expression(Expression),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct CharacterLiteral {
     pub value: char,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct BooleanLiteral {
     pub value: bool,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Identifier {
     pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binary {
+    pub left: Box<Expression>,
+    pub operator: BinaryOperator,
+    pub right: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LogicalAnd,
+    LogicalOr,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+    UnsignedRightShift,
+    // `??`, which gives its right side when the left side is null.
+    NullCoalescing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unary {
+    pub operator: UnaryOperator,
+    pub operand: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Plus,
+    LogicalNot,
+    BitwiseNot,
+    Increment,
+    Decrement,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Postfix {
+    pub operand: Box<Expression>,
+    pub operator: PostfixOperator,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostfixOperator {
+    Increment,
+    Decrement,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grouping {
+    pub expression: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub callee: Box<Expression>,
+    // `identity<int>(5)`. Left out, they are inferred from the arguments.
+    pub type_arguments: Vec<Type>,
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub object: Box<Expression>,
+    pub kind: MemberKind,
+    pub name: String,
+    pub span: Span,
+}
+
+// `.` accesses members of values, `->` members of objects and modules and
+// `::` members of namespaces. `?.` is `.` that gives `null` instead when
+// the value is null.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberKind {
+    Dot,
+    Arrow,
+    Path,
+    Optional,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Index {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct New {
+    pub class: Path,
+    pub type_arguments: Vec<Type>,
+    pub arguments: Vec<Expression>,
+    pub span: Span,
+}
+
+// `import("io")` used in an expression, which can only call a function of
+// the module, as in `import("io")->println(...)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub path: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lambda {
+    pub parameters: Vec<Param>,
+    pub body: LambdaBody,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LambdaBody {
+    Expression(Box<Expression>),
+    Block(Block),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -100,294 +498,1478 @@ pub enum Type {
     Array(Box<Type>),
     Map(Box<Type>, Box<Type>),
     Pointer(Box<Type>),
+    // `lambda<R>` leaves the parameters unchecked, `lambda<(A, B) => R>`
+    // spells them out.
+    Function(Option<Vec<Type>>, Box<Type>),
+    // The return type of functions that return nothing.
+    Void,
     Int,
+    Int64,
+    UInt,
+    UInt64,
+    Short,
+    Long,
+    Byte,
+    Float,
+    Double,
     String,
     Character,
     Boolean,
+    // `?`, which accepts a value of any type.
+    Any,
+    // `T?`, which also accepts `null`.
+    Optional(Box<Type>),
+    // A class or enum, possibly inside a namespace, or a type parameter.
+    // `Stack<int>` passes type arguments to a generic class.
+    Named(Path, Vec<Type>),
+}
+
+impl Type {
+    // The kind of number a numeric type holds, which decides its width and
+    // signedness.
+    pub fn number_kind(&self) -> Option<NumberKind> {
+        match self {
+            Type::Int => Some(NumberKind::Int),
+            Type::Int64 => Some(NumberKind::Int64),
+            Type::UInt => Some(NumberKind::UInt),
+            Type::UInt64 => Some(NumberKind::UInt64),
+            Type::Short => Some(NumberKind::Short),
+            Type::Long => Some(NumberKind::Long),
+            Type::Byte => Some(NumberKind::Byte),
+            Type::Float => Some(NumberKind::Float),
+            Type::Double => Some(NumberKind::Double),
+            _ => None,
+        }
+    }
+    // The size of a value of a primitive type in bits, or `None` for types
+    // without a fixed size.
+    pub fn bits(&self) -> Option<u32> {
+        match self {
+            Type::Boolean => Some(8),
+            Type::Character => Some(32),
+            _ => self.number_kind().map(|kind| kind.bits()),
+        }
+    }
+    pub fn is_signed(&self) -> bool {
+        self.number_kind().is_some_and(|kind| kind.is_signed())
+    }
+}
+
+impl From<NumberKind> for Type {
+    fn from(kind: NumberKind) -> Type {
+        match kind {
+            NumberKind::Int => Type::Int,
+            NumberKind::Int64 => Type::Int64,
+            NumberKind::UInt => Type::UInt,
+            NumberKind::UInt64 => Type::UInt64,
+            NumberKind::Short => Type::Short,
+            NumberKind::Long => Type::Long,
+            NumberKind::Byte => Type::Byte,
+            NumberKind::Float => Type::Float,
+            NumberKind::Double => Type::Double,
+        }
+    }
+}
+
+// Types are written the way they are in source, for error messages.
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Array(element) => write!(f, "array<{}>", element),
+            Type::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            Type::Pointer(target) => write!(f, "ptr<{}>", target),
+            Type::Function(None, return_type) => write!(f, "lambda<{}>", return_type),
+            Type::Function(Some(parameters), return_type) => {
+                let parameters: Vec<String> = parameters.iter().map(|parameter| parameter.to_string()).collect();
+                write!(f, "lambda<({}) => {}>", parameters.join(", "), return_type)
+            }
+            Type::Void => write!(f, "void"),
+            Type::Int => write!(f, "int"),
+            Type::Int64 => write!(f, "int64"),
+            Type::UInt => write!(f, "uint"),
+            Type::UInt64 => write!(f, "uint64"),
+            Type::Short => write!(f, "short"),
+            Type::Long => write!(f, "long"),
+            Type::Byte => write!(f, "byte"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::String => write!(f, "string"),
+            Type::Character => write!(f, "char"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Any => write!(f, "?"),
+            Type::Optional(type_) => write!(f, "{}?", type_),
+            Type::Named(path, arguments) if arguments.is_empty() => write!(f, "{}", path),
+            Type::Named(path, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+                write!(f, "{}<{}>", path, arguments.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub expected: Expected,
+    pub found: TokenType,
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Token(TokenType),
+    Statement,
+    Expression,
+    Type,
+    TypeArguments(usize),
+    NumberFitting(NumberKind),
+    EnclosingLoop,
+    AssignmentTarget,
+    Member,
+    OverridableMethod,
+    GenericDeclaration,
+    TopLevel,
+}
+
+impl ParseError {
+    fn new(expected: Expected, found: &Token) -> ParseError {
+        ParseError {
+            expected,
+            found: found.token_type.clone(),
+            value: found.text.clone(),
+            span: found.span,
+        }
+    }
 }
 
-pub fn parse(tokens: &Vec<Token>) -> Program {
-    Program { statements: parse_statements(&tokens) }
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.expected {
+            Expected::Token(token_type) => write!(f, "expected {:?}", token_type)?,
+            Expected::Statement => write!(f, "expected a statement")?,
+            Expected::Expression => write!(f, "expected an expression")?,
+            Expected::Type => write!(f, "expected a type")?,
+            Expected::TypeArguments(count) => write!(f, "expected {} type argument(s)", count)?,
+            Expected::NumberFitting(kind) => write!(f, "expected a number that fits in {:?}", kind)?,
+            Expected::EnclosingLoop => write!(f, "expected an enclosing loop")?,
+            Expected::AssignmentTarget => write!(f, "expected a variable to assign to")?,
+            Expected::Member => write!(f, "expected a field or method")?,
+            Expected::OverridableMethod => write!(f, "expected a method of Object to override")?,
+            Expected::GenericDeclaration => write!(f, "expected a function or class to take type parameters")?,
+            Expected::TopLevel => write!(f, "expected the top level or a namespace")?,
+        }
+        if self.value.is_empty() {
+            write!(f, ", found {:?}", self.found)
+        } else {
+            write!(f, ", found '{}'", self.value)
+        }
+    }
+}
+
+pub fn parse(tokens: &[Token]) -> Result<Program, Vec<ParseError>> {
+    let mut errors: Vec<ParseError> = Vec::new();
+    let statements = parse_statements(tokens, &mut errors);
+    if errors.is_empty() {
+        Ok(Program { statements })
+    } else {
+        Err(errors)
+    }
 }
 
 // Parse methods
-fn parse_statements(tokens: &Vec<Token>) -> Vec<Statement> {
-    let mut statements: Vec<Statement> = Vec::new();
+fn parse_statements(tokens: &[Token], errors: &mut Vec<ParseError>) -> Vec<Statement> {
     let mut index: usize = 0;
+    parse_statement_list(tokens, &mut index, errors, &TokenType::EndOfFile, true, false)
+}
+// Parses statements until `terminator` (or the end of the file) without
+// consuming it. Broken statements are recorded in `errors` and skipped.
+// `top_level` tells whether functions, classes, enums, namespaces and imports
+// can be declared, and `in_loop` whether `break` and `continue` are allowed.
+fn parse_statement_list(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>, terminator: &TokenType, top_level: bool, in_loop: bool) -> Vec<Statement> {
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut pending_doc: Option<String> = None;
 
-    while index < tokens.len() {
-        let token: &Token = &tokens[index];
-
-        match token.token_type {
-            TokenType::IdentifierLiteral => {
-                let name = token.value.clone();
-                expect_tok(&tokens, &mut index, TokenType::IdentifierLiteral);
-                if tokens[index].token_type == TokenType::OpenParenthesis {
-                    expect_tok(&tokens, &mut index, TokenType::OpenParenthesis);
-                    let arguments = parse_arguments(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::CloseParenthesis);
-                    expect_tok(&tokens, &mut index, TokenType::Semicolon);
-                    statements.push(Statement::FunctionCall(FunctionCall { name, arguments }));
-                    continue;
-                }
-                expect_tok(&tokens, &mut index, TokenType::Colon);
-                if match_tok(&tokens, &mut index, &TokenType::Func) {
-                    expect_tok(&tokens, &mut index, TokenType::LessThan);
-                    let return_type: Type = parse_type(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::GreaterThan);
-                    expect_tok(&tokens, &mut index, TokenType::Assignment);
-                    let parameters: Vec<(String, Type, bool, Expression)> = parse_parameters(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::FatArrow);
-                    expect_tok(&tokens, &mut index, TokenType::OpenBrace);
-                    let body: Vec<Statement> = parse_block(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::CloseBrace);
-
-                    statements.push(Statement::FunctionDeclaration(FunctionDeclaration {
-                        name,
-                        return_type,
-                        parameters,
-                        body,
-                    }));
-                } else {
-                    let type_ = parse_type(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::Assignment);
-                    let value = parse_expression(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::Semicolon);
-
-                    statements.push(Statement::VariableDeclaration(VariableDeclaration {
-                        name,
-                        type_,
-                        value,
-                        immutable: false,
-                    }));
+    while &tokens[*index].token_type != terminator && tokens[*index].token_type != TokenType::EndOfFile {
+        let token: &Token = &tokens[*index];
+
+        // Consecutive doc comments document the declaration that follows them.
+        if token.token_type == TokenType::DocComment {
+            pending_doc = Some(match pending_doc {
+                Some(doc) => doc + "\n" + &token.value,
+                None => token.value.clone(),
+            });
+            *index += 1;
+            continue;
+        }
+
+        let start: usize = *index;
+        let doc: Option<String> = pending_doc.take();
+        match parse_statement(tokens, index, doc, errors, in_loop) {
+            Ok(
+                Statement::FunctionDeclaration(_)
+                | Statement::ClassDeclaration(_)
+                | Statement::EnumDeclaration(_)
+                | Statement::Namespace(_)
+                | Statement::Import(_),
+            ) if !top_level => errors.push(ParseError::new(Expected::TopLevel, &tokens[start])),
+            Ok(statement) => statements.push(statement),
+            Err(error) => {
+                errors.push(error);
+                synchronize(tokens, index);
+                // A stray `}` stops synchronization without being consumed.
+                if *index == start {
+                    *index += 1;
                 }
             }
-            TokenType::Const => {
-                expect_tok(&tokens, &mut index, TokenType::Const);
-                if &tokens[index].token_type.clone() == &TokenType::OpenBracket {
-                    let methods: Expression = parse_expression(tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::Assignment);
-                    expect_tok(&tokens, &mut index, TokenType::Import);
-                    expect_tok(&tokens, &mut index, TokenType::OpenParenthesis);
-                    let path: String = tokens[index].value.clone().as_str().to_string();
-                    expect_tok(&tokens, &mut index, TokenType::StringLiteral);
-                    expect_tok(&tokens, &mut index, TokenType::CloseParenthesis);
-                    expect_tok(&tokens, &mut index, TokenType::Semicolon);
-
-                    statements.push(Statement::Import(Import { path, methods }));
-
-                    continue;
-                }
-                let name = token.value.clone();
-                expect_tok(&tokens, &mut index, TokenType::IdentifierLiteral);
-                if &tokens[index].token_type.clone() == &TokenType::Colon {
-                    expect_tok(&tokens, &mut index, TokenType::Colon);
-                    let type_ = parse_type(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::Assignment);
-                    let value = parse_expression(&tokens, &mut index);
-                    expect_tok(&tokens, &mut index, TokenType::Semicolon);
-                    statements.push(Statement::VariableDeclaration(VariableDeclaration {
-                        name,
-                        type_,
-                        value,
-                        immutable: true,
-                    }));
+        }
+    }
+
+    statements
+}
+fn parse_statement(tokens: &[Token], index: &mut usize, doc: Option<String>, errors: &mut Vec<ParseError>, in_loop: bool) -> Result<Statement, ParseError> {
+    let token: &Token = &tokens[*index];
+    let start: usize = *index;
+
+    match token.token_type {
+        TokenType::IdentifierLiteral if is_declaration(tokens, *index) => {
+            let name = token.value.clone();
+            expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+            let type_parameters: Vec<String> = parse_type_parameters(tokens, index)?;
+            expect_tok(tokens, index, TokenType::Colon)?;
+            let generic = matches!(tokens[*index].token_type, TokenType::Class | TokenType::Func);
+            if !type_parameters.is_empty() && !generic {
+                return Err(ParseError::new(Expected::GenericDeclaration, &tokens[*index]));
+            }
+            if match_tok(tokens, index, &TokenType::Class) {
+                parse_class(tokens, index, start, name, type_parameters, doc, errors)
+            } else if match_tok(tokens, index, &TokenType::Enum) {
+                parse_enum(tokens, index, start, name, doc, errors)
+            } else if match_tok(tokens, index, &TokenType::Namespace) {
+                expect_tok(tokens, index, TokenType::FatArrow)?;
+                let body_start: usize = *index;
+                expect_tok(tokens, index, TokenType::OpenBrace)?;
+                let statements = parse_statement_list(tokens, index, errors, &TokenType::CloseBrace, true, false);
+                expect_tok(tokens, index, TokenType::CloseBrace)?;
+                let body = Block {
+                    statements,
+                    span: span_from(tokens, body_start, *index),
+                };
+                Ok(Statement::Namespace(Namespace {
+                    name,
+                    body,
+                    doc,
+                    span: span_from(tokens, start, *index),
+                }))
+            } else if match_tok(tokens, index, &TokenType::Func) {
+                let func: Span = tokens[*index - 1].span;
+                let (mut arguments, arguments_span, assigned) = parse_type_arguments_and_assignment(tokens, index, 1)?;
+                expect_assigned(tokens, index, assigned)?;
+                let return_type: Type = arguments.remove(0);
+                let return_type_span = Span {
+                    end_byte: arguments_span.end_byte,
+                    ..func
+                };
+                let parameters: Vec<Param> = parse_parameters(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::FatArrow)?;
+                let body: Block = if tokens[*index].token_type == TokenType::OpenBrace && !is_map_literal(tokens, *index) {
+                    parse_block(tokens, index, errors, false)?
                 } else {
-                    let method_name = token.value.clone();
-                    expect_tok(&tokens, &mut index, TokenType::Assignment);
-                    expect_tok(&tokens, &mut index, TokenType::Import);
-                    expect_tok(&tokens, &mut index, TokenType::OpenParenthesis);
-                    let path: String = tokens[index].value.clone().as_str().to_string();
-                    expect_tok(&tokens, &mut index, TokenType::StringLiteral);
-                    expect_tok(&tokens, &mut index, TokenType::CloseParenthesis);
-                    expect_tok(&tokens, &mut index, TokenType::Semicolon);
-
-                    statements.push(Statement::Import(Import { path, methods: Expression::Identifier(Identifier { name: method_name }) }));
+                    // `identity<T>: func<T> = (x: T) => x;` returns the
+                    // expression, as a lambda would.
+                    let statement = match parse_simple_statement(tokens, index, errors)? {
+                        Statement::Expression(expression) => Statement::Return(Return {
+                            span: expression.span(),
+                            value: Some(expression),
+                        }),
+                        statement => statement,
+                    };
+                    expect_tok(tokens, index, TokenType::Semicolon)?;
+                    Block {
+                        span: statement.span(),
+                        statements: vec![statement],
+                    }
+                };
+
+                Ok(Statement::FunctionDeclaration(FunctionDeclaration {
+                    name,
+                    type_parameters,
+                    return_type,
+                    return_type_span,
+                    parameters,
+                    body,
+                    doc,
+                    span: span_from(tokens, start, *index),
+                }))
+            } else {
+                let (type_, type_span, assigned) = parse_type_and_assignment(tokens, index)?;
+                expect_assigned(tokens, index, assigned)?;
+                let value = parse_expression(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::Semicolon)?;
+
+                Ok(Statement::VariableDeclaration(VariableDeclaration {
+                    name,
+                    type_,
+                    type_span,
+                    value,
+                    immutable: false,
+                    doc,
+                    span: span_from(tokens, start, *index),
+                }))
+            }
+        }
+        TokenType::Export => {
+            expect_tok(tokens, index, TokenType::Export)?;
+            expect_tok(tokens, index, TokenType::Const)?;
+            let spec: ImportSpec = if tokens[*index].token_type == TokenType::OpenBrace {
+                parse_import_names(tokens, index)?
+            } else {
+                ImportSpec::Alias(parse_identifier(tokens, index)?)
+            };
+            parse_import(tokens, index, start, spec, true)
+        }
+        TokenType::Const => {
+            expect_tok(tokens, index, TokenType::Const)?;
+            if tokens[*index].token_type == TokenType::OpenBrace {
+                let spec: ImportSpec = parse_import_names(tokens, index)?;
+                return parse_import(tokens, index, start, spec, false);
+            }
+            let name = parse_identifier(tokens, index)?;
+            if tokens[*index].token_type == TokenType::Colon {
+                expect_tok(tokens, index, TokenType::Colon)?;
+                let (type_, type_span, assigned) = parse_type_and_assignment(tokens, index)?;
+                expect_assigned(tokens, index, assigned)?;
+                let value = parse_expression(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::Semicolon)?;
+                Ok(Statement::VariableDeclaration(VariableDeclaration {
+                    name: name.name,
+                    type_,
+                    type_span,
+                    value,
+                    immutable: true,
+                    doc,
+                    span: span_from(tokens, start, *index),
+                }))
+            } else {
+                parse_import(tokens, index, start, ImportSpec::Alias(name), false)
+            }
+        }
+        TokenType::Return => {
+            expect_tok(tokens, index, TokenType::Return)?;
+            let value = if tokens[*index].token_type == TokenType::Semicolon {
+                None
+            } else {
+                Some(parse_expression(tokens, index, errors)?)
+            };
+            expect_tok(tokens, index, TokenType::Semicolon)?;
+            Ok(Statement::Return(Return {
+                value,
+                span: span_from(tokens, start, *index),
+            }))
+        }
+        TokenType::OpenBrace if !is_map_literal(tokens, *index) => Ok(Statement::Block(parse_block(tokens, index, errors, in_loop)?)),
+        TokenType::If => parse_if(tokens, index, errors, in_loop),
+        TokenType::While => {
+            expect_tok(tokens, index, TokenType::While)?;
+            expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+            let condition = parse_expression(tokens, index, errors)?;
+            expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+            let body = parse_block(tokens, index, errors, true)?;
+            Ok(Statement::While(While {
+                condition,
+                body,
+                span: span_from(tokens, start, *index),
+            }))
+        }
+        TokenType::For => parse_for(tokens, index, errors),
+        TokenType::Break | TokenType::Continue => {
+            if !in_loop {
+                return Err(ParseError::new(Expected::EnclosingLoop, token));
+            }
+            *index += 1;
+            expect_tok(tokens, index, TokenType::Semicolon)?;
+            let span = span_from(tokens, start, *index);
+            if token.token_type == TokenType::Break {
+                Ok(Statement::Break(span))
+            } else {
+                Ok(Statement::Continue(span))
+            }
+        }
+        _ => {
+            let statement = parse_simple_statement(tokens, index, errors)?;
+            expect_tok(tokens, index, TokenType::Semicolon)?;
+            Ok(statement)
+        }
+    }
+}
+// A declaration starts with `name:`, or with `name<T, U>:` when it is
+// generic.
+fn is_declaration(tokens: &[Token], index: usize) -> bool {
+    match tokens[index + 1].token_type {
+        TokenType::Colon => true,
+        TokenType::LessThan => {
+            let mut index = index + 1;
+            parse_type_parameters(tokens, &mut index).is_ok() && tokens[index].token_type == TokenType::Colon
+        }
+        _ => false,
+    }
+}
+// `<T, U>`, or nothing for a declaration that is not generic.
+fn parse_type_parameters(tokens: &[Token], index: &mut usize) -> Result<Vec<String>, ParseError> {
+    let mut parameters: Vec<String> = Vec::new();
+    if !match_tok(tokens, index, &TokenType::LessThan) {
+        return Ok(parameters);
+    }
+    loop {
+        parameters.push(tokens[*index].value.clone());
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        if !match_tok(tokens, index, &TokenType::Comma) {
+            break;
+        }
+    }
+    expect_tok(tokens, index, TokenType::GreaterThan)?;
+    Ok(parameters)
+}
+// `{ print, println as say }`
+fn parse_import_names(tokens: &[Token], index: &mut usize) -> Result<ImportSpec, ParseError> {
+    expect_tok(tokens, index, TokenType::OpenBrace)?;
+    let mut names: Vec<(Identifier, Option<Identifier>)> = Vec::new();
+    while tokens[*index].token_type != TokenType::CloseBrace {
+        let name = parse_identifier(tokens, index)?;
+        let rename = if is_as(&tokens[*index]) {
+            *index += 1;
+            Some(parse_identifier(tokens, index)?)
+        } else {
+            None
+        };
+        names.push((name, rename));
+        if !match_tok(tokens, index, &TokenType::Comma) {
+            break;
+        }
+    }
+    expect_tok(tokens, index, TokenType::CloseBrace)?;
+    Ok(ImportSpec::Named(names))
+}
+// A name, with where it was written.
+fn parse_identifier(tokens: &[Token], index: &mut usize) -> Result<Identifier, ParseError> {
+    let token = &tokens[*index];
+    expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+    Ok(Identifier {
+        name: token.value.clone(),
+        span: token.span,
+    })
+}
+// `as` is only a keyword inside an import list.
+fn is_as(token: &Token) -> bool {
+    token.token_type == TokenType::IdentifierLiteral && token.value == "as"
+}
+// The `= import("path");` that ends an import, which started at `start`.
+fn parse_import(tokens: &[Token], index: &mut usize, start: usize, spec: ImportSpec, exported: bool) -> Result<Statement, ParseError> {
+    expect_tok(tokens, index, TokenType::Assignment)?;
+    expect_tok(tokens, index, TokenType::Import)?;
+    expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+    let path: String = tokens[*index].value.clone();
+    expect_tok(tokens, index, TokenType::StringLiteral)?;
+    expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+    expect_tok(tokens, index, TokenType::Semicolon)?;
+
+    Ok(Statement::Import(Import {
+        path,
+        spec,
+        exported,
+        span: span_from(tokens, start, *index),
+    }))
+}
+fn parse_class(tokens: &[Token], index: &mut usize, start: usize, name: String, type_parameters: Vec<String>, doc: Option<String>, errors: &mut Vec<ParseError>) -> Result<Statement, ParseError> {
+    expect_tok(tokens, index, TokenType::Assignment)?;
+    let parameters = parse_parameters(tokens, index, errors)?;
+    expect_tok(tokens, index, TokenType::FatArrow)?;
+    expect_tok(tokens, index, TokenType::OpenBrace)?;
+
+    let mut fields: Vec<VariableDeclaration> = Vec::new();
+    let mut methods: Vec<FunctionDeclaration> = Vec::new();
+    let mut overrides: Vec<String> = Vec::new();
+    let mut pending_doc: Option<String> = None;
+
+    while tokens[*index].token_type != TokenType::CloseBrace && tokens[*index].token_type != TokenType::EndOfFile {
+        if tokens[*index].token_type == TokenType::DocComment {
+            pending_doc = Some(match pending_doc {
+                Some(doc) => doc + "\n" + &tokens[*index].value,
+                None => tokens[*index].value.clone(),
+            });
+            *index += 1;
+            continue;
+        }
+
+        let member_start: usize = *index;
+        let overrides_member = match_tok(tokens, index, &TokenType::Override);
+        let name_token: &Token = &tokens[*index];
+        let member = match tokens[*index].token_type {
+            TokenType::IdentifierLiteral | TokenType::Const => {
+                parse_statement(tokens, index, pending_doc.take(), errors, false)
+            }
+            _ => Err(ParseError::new(Expected::Member, name_token)),
+        };
+        let member_name = match member {
+            Ok(Statement::VariableDeclaration(field)) => {
+                let name = field.name.clone();
+                fields.push(field);
+                name
+            }
+            Ok(Statement::FunctionDeclaration(method)) => {
+                let name = method.name.clone();
+                methods.push(method);
+                name
+            }
+            Ok(_) => {
+                errors.push(ParseError::new(Expected::Member, name_token));
+                continue;
+            }
+            Err(error) => {
+                errors.push(error);
+                synchronize(tokens, index);
+                if *index == member_start {
+                    *index += 1;
                 }
+                continue;
             }
-            TokenType::Return => {
-                expect_tok(&tokens, &mut index, TokenType::Return);
-                let value = parse_expression(&tokens, &mut index);
-                expect_tok(&tokens, &mut index, TokenType::Semicolon);
-                statements.push(Statement::Return(Return { value }));
+        };
+
+        let overridable = OBJECT_METHODS.contains(&member_name.as_str());
+        if overrides_member && !overridable {
+            errors.push(ParseError::new(Expected::OverridableMethod, name_token));
+        } else if !overrides_member && overridable {
+            errors.push(ParseError::new(Expected::Token(TokenType::Override), name_token));
+        }
+        if overrides_member {
+            overrides.push(member_name);
+        }
+    }
+    expect_tok(tokens, index, TokenType::CloseBrace)?;
+
+    Ok(Statement::ClassDeclaration(ClassDeclaration {
+        name,
+        type_parameters,
+        parameters,
+        fields,
+        methods,
+        overrides,
+        doc,
+        span: span_from(tokens, start, *index),
+    }))
+}
+fn parse_enum(tokens: &[Token], index: &mut usize, start: usize, name: String, doc: Option<String>, errors: &mut Vec<ParseError>) -> Result<Statement, ParseError> {
+    let mut variants: Vec<EnumVariant> = Vec::new();
+
+    if match_tok(tokens, index, &TokenType::FatArrow) {
+        loop {
+            variants.push(EnumVariant {
+                name: tokens[*index].value.clone(),
+                arguments: Vec::new(),
+                span: tokens[*index].span,
+            });
+            expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+            if !match_tok(tokens, index, &TokenType::Comma) {
+                break;
             }
-            TokenType::EndOfFile => break,
-            _ => panic!("Unhandled token: {:?}", token),
         }
+        expect_tok(tokens, index, TokenType::Semicolon)?;
+
+        return Ok(Statement::EnumDeclaration(EnumDeclaration {
+            name,
+            parameters: Vec::new(),
+            variants,
+            doc,
+            span: span_from(tokens, start, *index),
+        }));
     }
 
-    statements
+    expect_tok(tokens, index, TokenType::Assignment)?;
+    let parameters = parse_parameters(tokens, index, errors)?;
+    expect_tok(tokens, index, TokenType::FatArrow)?;
+    expect_tok(tokens, index, TokenType::OpenBrace)?;
+    while tokens[*index].token_type != TokenType::CloseBrace {
+        let variant_start: usize = *index;
+        let variant = tokens[*index].value.clone();
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        let arguments = if match_tok(tokens, index, &TokenType::OpenParenthesis) {
+            let arguments = parse_arguments(tokens, index, errors)?;
+            expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+            arguments
+        } else {
+            Vec::new()
+        };
+        variants.push(EnumVariant {
+            name: variant,
+            arguments,
+            span: span_from(tokens, variant_start, *index),
+        });
+        if !match_tok(tokens, index, &TokenType::Comma) {
+            break;
+        }
+    }
+    expect_tok(tokens, index, TokenType::CloseBrace)?;
+
+    Ok(Statement::EnumDeclaration(EnumDeclaration {
+        name,
+        parameters,
+        variants,
+        doc,
+        span: span_from(tokens, start, *index),
+    }))
+}
+// A statement that is not terminated by a `;` of its own, as used by the
+// step of a `for` loop.
+fn parse_simple_statement(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Statement, ParseError> {
+    let start: usize = *index;
+    let expression = match parse_expression(tokens, index, errors) {
+        Ok(expression) => expression,
+        Err(_) if *index == start => return Err(ParseError::new(Expected::Statement, &tokens[start])),
+        Err(error) => return Err(error),
+    };
+
+    let Some(operator) = assign_operator(&tokens[*index].token_type) else {
+        return Ok(Statement::Expression(expression));
+    };
+    if !matches!(expression, Expression::Identifier(_) | Expression::Path(_) | Expression::Member(_) | Expression::Index(_)) {
+        return Err(ParseError::new(Expected::AssignmentTarget, &tokens[start]));
+    }
+    let operator_span: Span = tokens[*index].span;
+    *index += 1;
+    let value = parse_expression(tokens, index, errors)?;
+    Ok(Statement::Assign(Assign {
+        target: expression,
+        operator,
+        operator_span,
+        value,
+        span: span_from(tokens, start, *index),
+    }))
+}
+fn parse_if(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>, in_loop: bool) -> Result<Statement, ParseError> {
+    let start: usize = *index;
+    expect_tok(tokens, index, TokenType::If)?;
+    expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+    let condition = parse_expression(tokens, index, errors)?;
+    expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+    let body = parse_block(tokens, index, errors, in_loop)?;
+    let else_body = if match_tok(tokens, index, &TokenType::Else) {
+        if tokens[*index].token_type == TokenType::If {
+            Some(Box::new(parse_if(tokens, index, errors, in_loop)?))
+        } else {
+            Some(Box::new(Statement::Block(parse_block(tokens, index, errors, in_loop)?)))
+        }
+    } else {
+        None
+    };
+    Ok(Statement::If(If {
+        condition,
+        body,
+        else_body,
+        span: span_from(tokens, start, *index),
+    }))
+}
+fn parse_for(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Statement, ParseError> {
+    let start: usize = *index;
+    expect_tok(tokens, index, TokenType::For)?;
+    expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+
+    if is_for_in(tokens, *index) {
+        let name = tokens[*index].value.clone();
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        let (type_, type_span) = if match_tok(tokens, index, &TokenType::Colon) {
+            let type_start: usize = *index;
+            (Some(parse_type(tokens, index)?), Some(span_from(tokens, type_start, *index)))
+        } else {
+            (None, None)
+        };
+        // `in` is not a keyword, so it is lexed as an identifier.
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        let iterable = parse_expression(tokens, index, errors)?;
+        expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+        let body = parse_block(tokens, index, errors, true)?;
+        return Ok(Statement::ForIn(ForIn {
+            name,
+            type_,
+            type_span,
+            iterable,
+            body,
+            span: span_from(tokens, start, *index),
+        }));
+    }
+
+    let init = if match_tok(tokens, index, &TokenType::Semicolon) {
+        None
+    } else {
+        Some(Box::new(parse_statement(tokens, index, None, errors, false)?))
+    };
+    let condition = if tokens[*index].token_type == TokenType::Semicolon {
+        None
+    } else {
+        Some(parse_expression(tokens, index, errors)?)
+    };
+    expect_tok(tokens, index, TokenType::Semicolon)?;
+    let step = if tokens[*index].token_type == TokenType::CloseParenthesis {
+        None
+    } else {
+        Some(Box::new(parse_simple_statement(tokens, index, errors)?))
+    };
+    expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+    let body = parse_block(tokens, index, errors, true)?;
+    Ok(Statement::For(For {
+        init,
+        condition,
+        step,
+        body,
+        span: span_from(tokens, start, *index),
+    }))
+}
+// Tells `for (x in xs)` and `for (x: T in xs)` apart from a C-style loop
+// whose init declares a variable.
+fn is_for_in(tokens: &[Token], mut index: usize) -> bool {
+    if tokens[index].token_type != TokenType::IdentifierLiteral {
+        return false;
+    }
+    index += 1;
+    if tokens[index].token_type == TokenType::Colon {
+        while !matches!(
+            tokens[index].token_type,
+            TokenType::Assignment | TokenType::Semicolon | TokenType::CloseParenthesis | TokenType::EndOfFile
+        ) && !is_in(&tokens[index])
+        {
+            index += 1;
+        }
+    }
+    is_in(&tokens[index])
+}
+fn is_in(token: &Token) -> bool {
+    token.token_type == TokenType::IdentifierLiteral && token.value == "in"
+}
+fn parse_type(tokens: &[Token], index: &mut usize) -> Result<Type, ParseError> {
+    let mut closers = Closers::default();
+    let type_ = parse_nested_type(tokens, index, &mut closers)?;
+    expect_no_closers(tokens, index, &closers)?;
+    Ok(type_)
+}
+// Parses a type and an `=` after it if there is one, which the lexer may have
+// merged with the type's last `>` into `>=`, `>>=` or `>>>=`. Returns whether
+// the `=` was there.
+fn parse_type_and_assignment(tokens: &[Token], index: &mut usize) -> Result<(Type, Span, bool), ParseError> {
+    let start: usize = *index;
+    let mut closers = Closers::default();
+    let type_ = parse_nested_type(tokens, index, &mut closers)?;
+    let span = type_span(tokens, start, *index, &closers);
+    let assigned = match_assignment(tokens, index, &mut closers);
+    expect_no_closers(tokens, index, &closers)?;
+    Ok((type_, span, assigned))
+}
+fn parse_type_arguments_and_assignment(tokens: &[Token], index: &mut usize, count: usize) -> Result<(Vec<Type>, Span, bool), ParseError> {
+    let start: usize = *index;
+    let mut closers = Closers::default();
+    let arguments = parse_nested_type_arguments(tokens, index, &mut closers, count)?;
+    let span = type_span(tokens, start, *index, &closers);
+    let assigned = match_assignment(tokens, index, &mut closers);
+    expect_no_closers(tokens, index, &closers)?;
+    Ok((arguments, span, assigned))
+}
+// The span of a type that ends before `index`, leaving out an `=` the lexer
+// merged into its last `>`.
+fn type_span(tokens: &[Token], start: usize, index: usize, closers: &Closers) -> Span {
+    let mut span = span_from(tokens, start, index);
+    if closers.assignment {
+        span.end_byte -= 1;
+    }
+    span
+}
+// Type arguments of a generic class or function, which can be any number.
+fn parse_type_list(tokens: &[Token], index: &mut usize) -> Result<Vec<Type>, ParseError> {
+    let mut closers = Closers::default();
+    let arguments = parse_nested_type_list(tokens, index, &mut closers)?;
+    expect_no_closers(tokens, index, &closers)?;
+    Ok(arguments)
+}
+fn parse_nested_type_arguments(tokens: &[Token], index: &mut usize, closers: &mut Closers, count: usize) -> Result<Vec<Type>, ParseError> {
+    let start: usize = *index;
+    let arguments = parse_nested_type_list(tokens, index, closers)?;
+    if arguments.len() != count {
+        return Err(ParseError::new(Expected::TypeArguments(count), &tokens[start]));
+    }
+    Ok(arguments)
+}
+// What is left of a token such as `>>` or `>>=` after its first `>` closed a
+// type argument list.
+#[derive(Default)]
+struct Closers {
+    // `>` characters that still have to close enclosing type argument lists.
+    angles: usize,
+    // Whether the token ended in an `=` that follows the whole type.
+    assignment: bool,
 }
-fn parse_type(tokens: &Vec<Token>, index: &mut usize) -> Type {
+impl Closers {
+    fn pending(&self) -> bool {
+        self.angles > 0 || self.assignment
+    }
+}
+fn parse_nested_type_list(tokens: &[Token], index: &mut usize, closers: &mut Closers) -> Result<Vec<Type>, ParseError> {
+    expect_tok(tokens, index, TokenType::LessThan)?;
+    let mut arguments: Vec<Type> = Vec::new();
+    loop {
+        arguments.push(parse_nested_type(tokens, index, closers)?);
+        if closers.pending() || !match_tok(tokens, index, &TokenType::Comma) {
+            break;
+        }
+    }
+    expect_closing_angle(tokens, index, closers)?;
+    Ok(arguments)
+}
+fn parse_nested_type(tokens: &[Token], index: &mut usize, closers: &mut Closers) -> Result<Type, ParseError> {
     let token: &Token = &tokens[*index];
 
-    match token.token_type {
+    let type_ = match token.token_type {
         TokenType::Array => {
-            expect_tok(&tokens, index, TokenType::Array);
-            expect_tok(&tokens, index, TokenType::LessThan);
-            let type_ = parse_type(&tokens, index);
-            expect_tok(&tokens, index, TokenType::GreaterThan);
-            Type::Array(Box::new(type_))
+            expect_tok(tokens, index, TokenType::Array)?;
+            let mut arguments = parse_nested_type_arguments(tokens, index, closers, 1)?;
+            Type::Array(Box::new(arguments.remove(0)))
         }
         TokenType::Map => {
-            expect_tok(&tokens, index, TokenType::Map);
-            expect_tok(&tokens, index, TokenType::LessThan);
-            let key_type = parse_type(&tokens, index);
-            expect_tok(&tokens, index, TokenType::Comma);
-            let value_type = parse_type(&tokens, index);
-            expect_tok(&tokens, index, TokenType::GreaterThan);
+            expect_tok(tokens, index, TokenType::Map)?;
+            let mut arguments = parse_nested_type_arguments(tokens, index, closers, 2)?;
+            let value_type = arguments.remove(1);
+            let key_type = arguments.remove(0);
             Type::Map(Box::new(key_type), Box::new(value_type))
         }
         TokenType::Pointer => {
-            expect_tok(&tokens, index, TokenType::Pointer);
-            expect_tok(&tokens, index, TokenType::LessThan);
-            let type_ = parse_type(&tokens, index);
-            expect_tok(&tokens, index, TokenType::GreaterThan);
-            Type::Pointer(Box::new(type_))
+            expect_tok(tokens, index, TokenType::Pointer)?;
+            let mut arguments = parse_nested_type_arguments(tokens, index, closers, 1)?;
+            Type::Pointer(Box::new(arguments.remove(0)))
         }
-        TokenType::Int32 => {
-            expect_tok(&tokens, index, TokenType::Int32);
-            Type::Int
+        TokenType::Lambda => {
+            expect_tok(tokens, index, TokenType::Lambda)?;
+            expect_tok(tokens, index, TokenType::LessThan)?;
+            let parameters = if match_tok(tokens, index, &TokenType::OpenParenthesis) {
+                let mut parameters: Vec<Type> = Vec::new();
+                while tokens[*index].token_type != TokenType::CloseParenthesis {
+                    parameters.push(parse_type(tokens, index)?);
+                    if !match_tok(tokens, index, &TokenType::Comma) {
+                        break;
+                    }
+                }
+                expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+                expect_tok(tokens, index, TokenType::FatArrow)?;
+                Some(parameters)
+            } else {
+                None
+            };
+            let return_type = parse_nested_type(tokens, index, closers)?;
+            expect_closing_angle(tokens, index, closers)?;
+            Type::Function(parameters, Box::new(return_type))
         }
-        TokenType::String => {
-            expect_tok(&tokens, index, TokenType::String);
-            Type::String
+        TokenType::IdentifierLiteral => {
+            let path = parse_path(tokens, index)?;
+            let arguments = if tokens[*index].token_type == TokenType::LessThan {
+                parse_nested_type_list(tokens, index, closers)?
+            } else {
+                Vec::new()
+            };
+            Type::Named(path, arguments)
         }
-        TokenType::Char => {
-            expect_tok(&tokens, index, TokenType::Char);
-            Type::Character
+        _ => match primitive_type(&token.token_type) {
+            Some(type_) => {
+                *index += 1;
+                type_
+            }
+            None => return Err(ParseError::new(Expected::Type, token)),
+        },
+    };
+    // A `?` after a `>>` that closed several lists belongs to the outermost one.
+    if !closers.pending() && match_tok(tokens, index, &TokenType::QuestionMark) {
+        return Ok(Type::Optional(Box::new(type_)));
+    }
+    Ok(type_)
+}
+fn primitive_type(token_type: &TokenType) -> Option<Type> {
+    let type_ = match token_type {
+        TokenType::Void => Type::Void,
+        TokenType::Int32 => Type::Int,
+        TokenType::Int64 => Type::Int64,
+        TokenType::UInt32 => Type::UInt,
+        TokenType::UInt64 => Type::UInt64,
+        TokenType::Short => Type::Short,
+        TokenType::Long => Type::Long,
+        TokenType::Byte => Type::Byte,
+        TokenType::Float => Type::Float,
+        TokenType::Double => Type::Double,
+        TokenType::String => Type::String,
+        TokenType::Char => Type::Character,
+        TokenType::Boolean => Type::Boolean,
+        TokenType::QuestionMark => Type::Any,
+        _ => return None,
+    };
+    Some(type_)
+}
+fn parse_path(tokens: &[Token], index: &mut usize) -> Result<Path, ParseError> {
+    let start: usize = *index;
+    let mut segments: Vec<String> = Vec::new();
+    loop {
+        segments.push(tokens[*index].value.clone());
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        if !match_tok(tokens, index, &TokenType::DoubleColon) {
+            break;
         }
-        TokenType::Boolean => {
-            expect_tok(&tokens, index, TokenType::Boolean);
-            Type::Boolean
+    }
+    Ok(Path {
+        segments,
+        span: span_from(tokens, start, *index),
+    })
+}
+fn parse_expression(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Expression, ParseError> {
+    parse_binary(tokens, index, errors, 0)
+}
+// Precedence climbing: only operators binding at least as tightly as
+// `min_precedence` are consumed here, so every binary operator is
+// left-associative.
+fn parse_binary(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>, min_precedence: u8) -> Result<Expression, ParseError> {
+    let start: usize = *index;
+    let mut left = parse_unary(tokens, index, errors)?;
+
+    while let Some((operator, precedence)) = binary_operator(&tokens[*index].token_type) {
+        if precedence < min_precedence {
+            break;
         }
-        _ => panic!("Unhandled token: {:?}", token),
+        *index += 1;
+        let right = parse_binary(tokens, index, errors, precedence + 1)?;
+        left = Expression::Binary(Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: span_from(tokens, start, *index),
+        });
     }
+
+    Ok(left)
+}
+fn parse_unary(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Expression, ParseError> {
+    let start: usize = *index;
+    let operator = match tokens[*index].token_type {
+        TokenType::Minus => UnaryOperator::Negate,
+        TokenType::Plus => UnaryOperator::Plus,
+        TokenType::LogicalNot => UnaryOperator::LogicalNot,
+        TokenType::BitwiseNot => UnaryOperator::BitwiseNot,
+        TokenType::Increment => UnaryOperator::Increment,
+        TokenType::Decrement => UnaryOperator::Decrement,
+        _ => return parse_postfix(tokens, index, errors),
+    };
+    *index += 1;
+    // A negated literal may be one past the largest value of its kind, so
+    // `-2147483648` is the smallest `int`.
+    let operand = if operator == UnaryOperator::Negate && is_negated_literal(tokens, *index) {
+        *index += 1;
+        Expression::NumberLiteral(parse_number_literal(&tokens[*index - 1], true)?)
+    } else {
+        parse_unary(tokens, index, errors)?
+    };
+    Ok(Expression::Unary(Unary {
+        operator,
+        operand: Box::new(operand),
+        span: span_from(tokens, start, *index),
+    }))
+}
+// An integer literal right after a `-`, with nothing after it that binds
+// tighter than the `-`.
+fn is_negated_literal(tokens: &[Token], index: usize) -> bool {
+    tokens[index].token_type == TokenType::NumberLiteral
+        && !matches!(
+            tokens[index + 1].token_type,
+            TokenType::OpenParenthesis
+                | TokenType::OpenBracket
+                | TokenType::Dot
+                | TokenType::Arrow
+                | TokenType::DoubleColon
+                | TokenType::QuestionDot
+                | TokenType::Increment
+                | TokenType::Decrement
+        )
 }
-fn parse_expression(tokens: &Vec<Token>, index: &mut usize) -> Expression {
+fn parse_postfix(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Expression, ParseError> {
+    let start: usize = *index;
+    let mut expression = parse_primary(tokens, index, errors)?;
+
+    loop {
+        let kind = match tokens[*index].token_type {
+            TokenType::OpenParenthesis | TokenType::LessThan if is_call(tokens, *index, &expression) => {
+                let type_arguments = if tokens[*index].token_type == TokenType::LessThan {
+                    parse_type_list(tokens, index)?
+                } else {
+                    Vec::new()
+                };
+                expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+                let arguments = parse_arguments(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+                expression = Expression::Call(Call {
+                    callee: Box::new(expression),
+                    type_arguments,
+                    arguments,
+                    span: span_from(tokens, start, *index),
+                });
+                continue;
+            }
+            TokenType::OpenBracket => {
+                expect_tok(tokens, index, TokenType::OpenBracket)?;
+                let index_expression = parse_expression(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::CloseBracket)?;
+                expression = Expression::Index(Index {
+                    object: Box::new(expression),
+                    index: Box::new(index_expression),
+                    span: span_from(tokens, start, *index),
+                });
+                continue;
+            }
+            TokenType::Dot => MemberKind::Dot,
+            TokenType::Arrow => MemberKind::Arrow,
+            TokenType::DoubleColon => MemberKind::Path,
+            TokenType::QuestionDot => MemberKind::Optional,
+            TokenType::Increment | TokenType::Decrement => {
+                let operator = if tokens[*index].token_type == TokenType::Increment {
+                    PostfixOperator::Increment
+                } else {
+                    PostfixOperator::Decrement
+                };
+                *index += 1;
+                expression = Expression::Postfix(Postfix {
+                    operand: Box::new(expression),
+                    operator,
+                    span: span_from(tokens, start, *index),
+                });
+                continue;
+            }
+            _ => break,
+        };
+        *index += 1;
+        let name = tokens[*index].value.clone();
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        expression = Expression::Member(Member {
+            object: Box::new(expression),
+            kind,
+            name,
+            span: span_from(tokens, start, *index),
+        });
+    }
+
+    Ok(expression)
+}
+fn parse_primary(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Expression, ParseError> {
     let token: &Token = &tokens[*index];
+    let start: usize = *index;
 
-    match token.token_type {
+    let expression = match token.token_type {
+        TokenType::OpenParenthesis | TokenType::IdentifierLiteral if is_lambda(tokens, *index) => {
+            Expression::Lambda(parse_lambda(tokens, index, errors)?)
+        }
+        TokenType::OpenParenthesis => {
+            expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+            let expression = parse_expression(tokens, index, errors)?;
+            expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+            Expression::Grouping(Grouping {
+                expression: Box::new(expression),
+                span: span_from(tokens, start, *index),
+            })
+        }
         TokenType::OpenBracket => {
-            expect_tok(&tokens, index, TokenType::OpenBracket);
+            expect_tok(tokens, index, TokenType::OpenBracket)?;
             let mut elements: Vec<Expression> = Vec::new();
             while tokens[*index].token_type != TokenType::CloseBracket {
-                elements.push(parse_expression(&tokens, index));
+                elements.push(parse_expression(tokens, index, errors)?);
                 if tokens[*index].token_type == TokenType::Comma {
-                    expect_tok(&tokens, index, TokenType::Comma);
+                    expect_tok(tokens, index, TokenType::Comma)?;
                 }
             }
 
-            expect_tok(&tokens, index, TokenType::CloseBracket);
+            expect_tok(tokens, index, TokenType::CloseBracket)?;
 
-            Expression::ArrayLiteral(ArrayLiteral { elements })
+            Expression::ArrayLiteral(ArrayLiteral {
+                elements,
+                span: span_from(tokens, start, *index),
+            })
         }
         TokenType::OpenBrace => {
-            expect_tok(&tokens, index, TokenType::OpenBrace);
+            expect_tok(tokens, index, TokenType::OpenBrace)?;
             let mut elements: Vec<(Expression, Expression)> = Vec::new();
             while tokens[*index].token_type != TokenType::CloseBrace {
-                let key = parse_expression(&tokens, index);
-                expect_tok(&tokens, index, TokenType::Colon);
-                let value = parse_expression(&tokens, index);
+                let key = parse_expression(tokens, index, errors)?;
+                expect_tok(tokens, index, TokenType::Colon)?;
+                let value = parse_expression(tokens, index, errors)?;
                 elements.push((key, value));
-                if *index + 1 > tokens.len() {
-                    break;
-                }
-                // this shit is broken asf, but only if you define a map inside of a function body???????
                 if tokens[*index].token_type != TokenType::CloseBrace {
-                    expect_tok(&tokens, index, TokenType::Comma);
+                    expect_tok(tokens, index, TokenType::Comma)?;
                 }
             }
-            expect_tok(&tokens, index, TokenType::CloseBrace);
-            Expression::MapLiteral(MapLiteral { elements })
-        }
-        TokenType::NumberLiteral => {
-            expect_tok(&tokens, index, TokenType::NumberLiteral);
-            Expression::NumberLiteral(NumberLiteral {
-                value: token.value.parse().unwrap(),
+            expect_tok(tokens, index, TokenType::CloseBrace)?;
+            Expression::MapLiteral(MapLiteral {
+                elements,
+                span: span_from(tokens, start, *index),
             })
         }
+        TokenType::NumberLiteral | TokenType::FloatLiteral => {
+            *index += 1;
+            Expression::NumberLiteral(parse_number_literal(token, false)?)
+        }
         TokenType::StringLiteral => {
-            expect_tok(&tokens, index, TokenType::StringLiteral);
+            expect_tok(tokens, index, TokenType::StringLiteral)?;
             Expression::StringLiteral(StringLiteral {
                 value: token.value.clone(),
+                span: token.span,
             })
         }
+        TokenType::TemplateLiteral => {
+            expect_tok(tokens, index, TokenType::TemplateLiteral)?;
+            let mut parts: Vec<TemplateElement> = Vec::new();
+            for part in &token.parts {
+                parts.push(match part {
+                    TemplatePart::Text(text, span) => TemplateElement::Text(text.clone(), *span),
+                    TemplatePart::Expression(expression_tokens) => {
+                        let mut expression_index: usize = 0;
+                        let expression = parse_expression(expression_tokens, &mut expression_index, errors).map_err(at_interpolation_end)?;
+                        if expression_tokens[expression_index].token_type != TokenType::EndOfFile {
+                            return Err(ParseError::new(Expected::Token(TokenType::CloseBrace), &expression_tokens[expression_index]));
+                        }
+                        TemplateElement::Expression(expression)
+                    }
+                });
+            }
+            Expression::Template(Template { parts, span: token.span })
+        }
         TokenType::CharLiteral => {
-            expect_tok(&tokens, index, TokenType::CharLiteral);
+            expect_tok(tokens, index, TokenType::CharLiteral)?;
             Expression::CharacterLiteral(CharacterLiteral {
                 value: token.value.chars().next().unwrap(),
+                span: token.span,
             })
         }
         TokenType::BooleanLiteral => {
-            expect_tok(&tokens, index, TokenType::BooleanLiteral);
+            expect_tok(tokens, index, TokenType::BooleanLiteral)?;
             Expression::BooleanLiteral(BooleanLiteral {
-                value: token.value.parse().unwrap(),
+                value: token.value == "true",
+                span: token.span,
             })
         }
+        TokenType::NullLiteral => {
+            expect_tok(tokens, index, TokenType::NullLiteral)?;
+            Expression::Null(token.span)
+        }
+        TokenType::IdentifierLiteral if tokens[*index + 1].token_type == TokenType::DoubleColon => {
+            Expression::Path(parse_path(tokens, index)?)
+        }
         TokenType::IdentifierLiteral => {
-            expect_tok(&tokens, index, TokenType::IdentifierLiteral);
+            expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
             Expression::Identifier(Identifier {
                 name: token.value.clone(),
+                span: token.span,
+            })
+        }
+        TokenType::New => {
+            expect_tok(tokens, index, TokenType::New)?;
+            let class = parse_path(tokens, index)?;
+            let type_arguments = if tokens[*index].token_type == TokenType::LessThan {
+                parse_type_list(tokens, index)?
+            } else {
+                Vec::new()
+            };
+            expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+            let arguments = parse_arguments(tokens, index, errors)?;
+            expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+            Expression::New(New {
+                class,
+                type_arguments,
+                arguments,
+                span: span_from(tokens, start, *index),
+            })
+        }
+        TokenType::This => {
+            expect_tok(tokens, index, TokenType::This)?;
+            Expression::This(token.span)
+        }
+        TokenType::Import => {
+            expect_tok(tokens, index, TokenType::Import)?;
+            expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+            let path: String = tokens[*index].value.clone();
+            expect_tok(tokens, index, TokenType::StringLiteral)?;
+            expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+            Expression::Module(Module {
+                path,
+                span: span_from(tokens, start, *index),
             })
         }
-        _ => Expression::None,
+        _ => return Err(ParseError::new(Expected::Expression, token)),
+    };
+    Ok(expression)
+}
+// Binary operators with their precedence, following C. Higher binds tighter.
+fn binary_operator(token_type: &TokenType) -> Option<(BinaryOperator, u8)> {
+    let operator = match token_type {
+        TokenType::NullCoalescing => (BinaryOperator::NullCoalescing, 1),
+        TokenType::LogicalOr => (BinaryOperator::LogicalOr, 2),
+        TokenType::LogicalAnd => (BinaryOperator::LogicalAnd, 3),
+        TokenType::BitwiseOr => (BinaryOperator::BitwiseOr, 4),
+        TokenType::BitwiseXor => (BinaryOperator::BitwiseXor, 5),
+        TokenType::BitwiseAnd => (BinaryOperator::BitwiseAnd, 6),
+        TokenType::Equal => (BinaryOperator::Equal, 7),
+        TokenType::NotEqual => (BinaryOperator::NotEqual, 7),
+        TokenType::LessThan => (BinaryOperator::LessThan, 8),
+        TokenType::LessThanOrEqual => (BinaryOperator::LessThanOrEqual, 8),
+        TokenType::GreaterThan => (BinaryOperator::GreaterThan, 8),
+        TokenType::GreaterThanOrEqual => (BinaryOperator::GreaterThanOrEqual, 8),
+        TokenType::BitwiseLeftShift => (BinaryOperator::LeftShift, 9),
+        TokenType::BitwiseRightShift => (BinaryOperator::RightShift, 9),
+        TokenType::BitwiseUnsignedRightShift => (BinaryOperator::UnsignedRightShift, 9),
+        TokenType::Plus => (BinaryOperator::Add, 10),
+        TokenType::Minus => (BinaryOperator::Subtract, 10),
+        TokenType::Multiply => (BinaryOperator::Multiply, 11),
+        TokenType::Divide => (BinaryOperator::Divide, 11),
+        TokenType::Modulo => (BinaryOperator::Modulo, 11),
+        _ => return None,
+    };
+    Some(operator)
+}
+fn parse_lambda(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Lambda, ParseError> {
+    let start: usize = *index;
+    let parameters = if tokens[*index].token_type == TokenType::OpenParenthesis {
+        parse_parameters(tokens, index, errors)?
+    } else {
+        // The single-parameter shorthand `x: T => ...`.
+        let name = tokens[*index].value.clone();
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        expect_tok(tokens, index, TokenType::Colon)?;
+        let type_start: usize = *index;
+        let type_ = parse_type(tokens, index)?;
+        vec![Param {
+            name,
+            type_,
+            type_span: span_from(tokens, type_start, *index),
+            optional: false,
+            default_value: None,
+            span: span_from(tokens, start, *index),
+        }]
+    };
+    expect_tok(tokens, index, TokenType::FatArrow)?;
+
+    let body = if tokens[*index].token_type == TokenType::OpenBrace && !is_map_literal(tokens, *index) {
+        LambdaBody::Block(parse_block(tokens, index, errors, false)?)
+    } else {
+        // Setters such as `(v: int) => this->value = v` assign in place of
+        // an expression.
+        match parse_simple_statement(tokens, index, errors)? {
+            Statement::Expression(expression) => LambdaBody::Expression(Box::new(expression)),
+            statement => LambdaBody::Block(Block {
+                span: statement.span(),
+                statements: vec![statement],
+            }),
+        }
+    };
+    Ok(Lambda {
+        parameters,
+        body,
+        span: span_from(tokens, start, *index),
+    })
+}
+// A lambda starts with `()` followed by `=>`, with `(name:` or `(name?:`, or
+// with `name: T =>` for the single-parameter shorthand.
+fn is_lambda(tokens: &[Token], index: usize) -> bool {
+    match tokens[index].token_type {
+        TokenType::OpenParenthesis => match tokens[index + 1].token_type {
+            TokenType::CloseParenthesis => tokens[index + 2].token_type == TokenType::FatArrow,
+            TokenType::IdentifierLiteral => matches!(tokens[index + 2].token_type, TokenType::Colon | TokenType::QuestionMark),
+            _ => false,
+        },
+        TokenType::IdentifierLiteral if tokens[index + 1].token_type == TokenType::Colon => {
+            let mut index = index + 2;
+            parse_type(tokens, &mut index).is_ok() && tokens[index].token_type == TokenType::FatArrow
+        }
+        _ => false,
+    }
+}
+// A `<` starts the type arguments of a call such as `identity<int>(5)` when
+// it follows a name, a list of types and a `(` follow it, and it touches the
+// name and the `(` touches the closing `>`. Otherwise it is a comparison, so
+// `f(a < b, c > (d))` passes two booleans while `f(a<b, c>(d))` is a call.
+fn is_call(tokens: &[Token], index: usize, callee: &Expression) -> bool {
+    if tokens[index].token_type == TokenType::OpenParenthesis {
+        return true;
+    }
+    let named = matches!(callee, Expression::Identifier(_) | Expression::Path(_) | Expression::Member(_));
+    let touches = |index: usize| tokens[index - 1].span.end_byte == tokens[index].span.start_byte;
+    let mut end = index;
+    named
+        && touches(index)
+        && parse_type_list(tokens, &mut end).is_ok()
+        && tokens[end].token_type == TokenType::OpenParenthesis
+        && touches(end)
+}
+// `{ "key": value }` is a map literal rather than a block.
+fn is_map_literal(tokens: &[Token], index: usize) -> bool {
+    matches!(
+        tokens[index + 1].token_type,
+        TokenType::StringLiteral
+            | TokenType::TemplateLiteral
+            | TokenType::NumberLiteral
+            | TokenType::FloatLiteral
+            | TokenType::CharLiteral
+            | TokenType::BooleanLiteral
+            | TokenType::NullLiteral
+    ) && tokens[index + 2].token_type == TokenType::Colon
+}
+// The tokens of an interpolated expression end in an `EndOfFile` where its
+// `}` was, which is what errors about it should say they found.
+fn at_interpolation_end(mut error: ParseError) -> ParseError {
+    if error.found == TokenType::EndOfFile {
+        error.found = TokenType::CloseBrace;
+        error.value = "}".to_string();
     }
+    error
+}
+fn assign_operator(token_type: &TokenType) -> Option<AssignOperator> {
+    let operator = match token_type {
+        TokenType::Assignment => AssignOperator::Assign,
+        TokenType::AddByValue => AssignOperator::Add,
+        TokenType::SubtractByValue => AssignOperator::Subtract,
+        TokenType::MultiplyByValue => AssignOperator::Multiply,
+        TokenType::DivideByValue => AssignOperator::Divide,
+        TokenType::ModuloByValue => AssignOperator::Modulo,
+        TokenType::BitwiseAndByValue => AssignOperator::BitwiseAnd,
+        TokenType::BitwiseOrByValue => AssignOperator::BitwiseOr,
+        TokenType::BitwiseXorByValue => AssignOperator::BitwiseXor,
+        TokenType::BitwiseNotByValue => AssignOperator::BitwiseNot,
+        TokenType::BitwiseLeftShiftByValue => AssignOperator::LeftShift,
+        TokenType::BitwiseRightShiftByValue => AssignOperator::RightShift,
+        TokenType::BitwiseUnsignedRightShiftByValue => AssignOperator::UnsignedRightShift,
+        _ => return None,
+    };
+    Some(operator)
+}
+fn parse_number_literal(token: &Token, negated: bool) -> Result<NumberLiteral, ParseError> {
+    // The lexer only produces well-formed number literals.
+    let (radix, digits, kind) = split_number_literal(&token.value).unwrap();
+
+    let value = if kind.is_float() {
+        Number::Float(digits.parse().unwrap())
+    } else {
+        // Decimal literals of signed kinds have to be positive values of the
+        // kind, while other radixes may spell out any bit pattern. Values
+        // are stored as `i128`, so `long` literals are always bounded by it.
+        let bits: u32 = if kind == NumberKind::Long || (radix == 10 && kind.is_signed()) {
+            kind.bits() - 1
+        } else {
+            kind.bits()
+        };
+        let smallest = |integer: u128| negated && radix == 10 && kind.is_signed() && integer == 1 << bits;
+        match u128::from_str_radix(digits, radix) {
+            Ok(integer) if integer >> bits == 0 || smallest(integer) => Number::Integer(integer),
+            _ => return Err(ParseError::new(Expected::NumberFitting(kind), token)),
+        }
+    };
+    Ok(NumberLiteral {
+        value,
+        kind,
+        span: token.span,
+    })
 }
-fn parse_parameters(tokens: &Vec<Token>, index: &mut usize) -> Vec<(String, Type, bool, Expression)> {
-    expect_tok(&tokens, index, TokenType::OpenParenthesis);
-    let mut parameters: Vec<(String, Type, bool, Expression)> = Vec::new();
+fn parse_parameters(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Vec<Param>, ParseError> {
+    expect_tok(tokens, index, TokenType::OpenParenthesis)?;
+    let mut parameters: Vec<Param> = Vec::new();
     while tokens[*index].token_type != TokenType::CloseParenthesis {
+        let start: usize = *index;
         let name = tokens[*index].value.clone();
         let mut optional: bool = false;
-        let mut default_value: Expression = Expression::None;
-        expect_tok(&tokens, index, TokenType::IdentifierLiteral);
-        if match_tok(&tokens, index, &TokenType::QuestionMark) {
+        let mut default_value: Option<Expression> = None;
+        expect_tok(tokens, index, TokenType::IdentifierLiteral)?;
+        if match_tok(tokens, index, &TokenType::QuestionMark) {
             optional = true;
         }
-        expect_tok(&tokens, index, TokenType::Colon);
-        let type_ = parse_type(&tokens, index);
-        if match_tok(&tokens, index, &TokenType::Assignment) {
-            default_value = parse_expression(&tokens, index);
+        expect_tok(tokens, index, TokenType::Colon)?;
+        let (mut type_, type_span, assigned) = parse_type_and_assignment(tokens, index)?;
+        // An optional parameter without a default is null when omitted.
+        if optional && !matches!(type_, Type::Optional(_)) {
+            type_ = Type::Optional(Box::new(type_));
+        }
+        if assigned {
+            default_value = Some(parse_expression(tokens, index, errors)?);
             optional = true;
         }
-        parameters.push((name, type_, optional, default_value));
+        parameters.push(Param {
+            name,
+            type_,
+            type_span,
+            optional,
+            default_value,
+            span: span_from(tokens, start, *index),
+        });
         if tokens[*index].token_type == TokenType::Comma {
-            expect_tok(&tokens, index, TokenType::Comma);
+            expect_tok(tokens, index, TokenType::Comma)?;
         }
     }
-    expect_tok(&tokens, index, TokenType::CloseParenthesis);
-    parameters
+    expect_tok(tokens, index, TokenType::CloseParenthesis)?;
+    Ok(parameters)
 }
-fn parse_block(tokens: &Vec<Token>, index: &mut usize) -> Vec<Statement> {
-    let mut toks: Vec<Token> = Vec::new();
-    let mut depth: usize = 0;
-    while tokens[*index].token_type != TokenType::CloseBrace || depth != 0 {
-        if tokens[*index].token_type == TokenType::OpenBrace {
-            depth += 1;
-        } else if tokens[*index].token_type == TokenType::CloseBrace {
-            depth -= 1;
-        }
-        toks.push(tokens[*index].clone());
-        *index += 1;
-    }
-    let statements = parse_statements(&toks);
-    statements
+fn parse_block(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>, in_loop: bool) -> Result<Block, ParseError> {
+    let start: usize = *index;
+    expect_tok(tokens, index, TokenType::OpenBrace)?;
+    let statements: Vec<Statement> = parse_statement_list(tokens, index, errors, &TokenType::CloseBrace, false, in_loop);
+    expect_tok(tokens, index, TokenType::CloseBrace)?;
+    Ok(Block {
+        statements,
+        span: span_from(tokens, start, *index),
+    })
 }
-fn parse_arguments(tokens: &Vec<Token>, index: &mut usize) -> Vec<Expression> {
+fn parse_arguments(tokens: &[Token], index: &mut usize, errors: &mut Vec<ParseError>) -> Result<Vec<Expression>, ParseError> {
     let mut arguments: Vec<Expression> = Vec::new();
     while tokens[*index].token_type != TokenType::CloseParenthesis {
-        arguments.push(parse_expression(&tokens, index));
-        
-        if tokens[*index].token_type == TokenType::Comma {
-            expect_tok(&tokens, index, TokenType::Comma);
+        arguments.push(parse_expression(tokens, index, errors)?);
+
+        if !match_tok(tokens, index, &TokenType::Comma) {
+            break;
+        }
+    }
+    Ok(arguments)
+}
+
+// Error recovery: skips the rest of a broken statement, stopping after the
+// next `;`, after the block the statement opened, or before the `}` that
+// closes the enclosing block.
+fn synchronize(tokens: &[Token], index: &mut usize) {
+    let mut depth: usize = 0;
+    while *index < tokens.len() {
+        match tokens[*index].token_type {
+            TokenType::EndOfFile => return,
+            TokenType::Semicolon if depth == 0 => {
+                *index += 1;
+                return;
+            }
+            TokenType::OpenBrace => depth += 1,
+            TokenType::CloseBrace => {
+                if depth == 0 {
+                    return;
+                }
+                *index += 1;
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+                continue;
+            }
+            _ => {}
         }
+        *index += 1;
     }
-    arguments
 }
 
 // Util methods
-fn match_tok(tokens: &Vec<Token>, index: &mut usize, token_type: &TokenType) -> bool {
+// The span from the start of `tokens[start]` to the end of the last token
+// before `index`.
+fn span_from(tokens: &[Token], start: usize, index: usize) -> Span {
+    let end: Span = tokens[index.max(start + 1) - 1].span;
+    Span {
+        end_byte: end.end_byte,
+        ..tokens[start].span
+    }
+}
+fn match_tok(tokens: &[Token], index: &mut usize, token_type: &TokenType) -> bool {
     if &tokens[*index].token_type == token_type {
         *index += 1;
         return true;
@@ -395,8 +1977,313 @@ fn match_tok(tokens: &Vec<Token>, index: &mut usize, token_type: &TokenType) ->
 
     false
 }
-fn expect_tok(tokens: &Vec<Token>, index: &mut usize, token_type: TokenType) {
+fn expect_closing_angle(tokens: &[Token], index: &mut usize, closers: &mut Closers) -> Result<(), ParseError> {
+    if closers.angles > 0 {
+        closers.angles -= 1;
+        return Ok(());
+    }
+    if closers.assignment {
+        return Err(ParseError::new(Expected::Token(TokenType::GreaterThan), &tokens[*index - 1]));
+    }
+
+    let (angles, assignment) = match tokens[*index].token_type {
+        TokenType::GreaterThan => (0, false),
+        TokenType::BitwiseRightShift => (1, false),
+        TokenType::BitwiseUnsignedRightShift => (2, false),
+        TokenType::GreaterThanOrEqual => (0, true),
+        TokenType::BitwiseRightShiftByValue => (1, true),
+        TokenType::BitwiseUnsignedRightShiftByValue => (2, true),
+        _ => return Err(ParseError::new(Expected::Token(TokenType::GreaterThan), &tokens[*index])),
+    };
+    *index += 1;
+    closers.angles += angles;
+    closers.assignment = assignment;
+    Ok(())
+}
+fn match_assignment(tokens: &[Token], index: &mut usize, closers: &mut Closers) -> bool {
+    if closers.angles == 0 && closers.assignment {
+        closers.assignment = false;
+        return true;
+    }
+    !closers.pending() && match_tok(tokens, index, &TokenType::Assignment)
+}
+fn expect_no_closers(tokens: &[Token], index: &mut usize, closers: &Closers) -> Result<(), ParseError> {
+    if closers.pending() {
+        return Err(ParseError::new(Expected::Token(TokenType::Semicolon), &tokens[*index - 1]));
+    }
+    Ok(())
+}
+fn expect_assigned(tokens: &[Token], index: &mut usize, assigned: bool) -> Result<(), ParseError> {
+    if !assigned {
+        return Err(ParseError::new(Expected::Token(TokenType::Assignment), &tokens[*index]));
+    }
+    Ok(())
+}
+fn expect_tok(tokens: &[Token], index: &mut usize, token_type: TokenType) -> Result<(), ParseError> {
     if !match_tok(tokens, index, &token_type) {
-        panic!("Expected token: {:?}, got {:?}", token_type.clone(), tokens[*index].token_type);
+        return Err(ParseError::new(Expected::Token(token_type), &tokens[*index]));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+
+    fn parse_source(source: &str) -> Result<Program, Vec<ParseError>> {
+        parse(&lex(source.to_string()).unwrap())
+    }
+
+    fn error_lines(source: &str) -> Vec<(usize, Expected)> {
+        parse_source(source)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.span.line, error.expected))
+            .collect()
+    }
+
+    // An expression with every operator application in parentheses, taking
+    // operators and operands from `source`.
+    fn grouped(expression: &Expression, source: &str) -> String {
+        let text = |start: usize, end: usize| source[start..end].trim().to_string();
+        match expression {
+            Expression::Binary(binary) => format!(
+                "({} {} {})",
+                grouped(&binary.left, source),
+                text(binary.left.span().end_byte, binary.right.span().start_byte),
+                grouped(&binary.right, source)
+            ),
+            Expression::Unary(unary) => format!("({} {})", text(unary.span.start_byte, unary.operand.span().start_byte), grouped(&unary.operand, source)),
+            Expression::Postfix(postfix) => format!("({} {})", grouped(&postfix.operand, source), text(postfix.operand.span().end_byte, postfix.span.end_byte)),
+            Expression::Grouping(grouping) => grouped(&grouping.expression, source),
+            expression => text(expression.span().start_byte, expression.span().end_byte),
+        }
+    }
+
+    #[test]
+    fn operators_group_by_precedence_and_associativity() {
+        let table = [
+            ("1-2-3", "((1 - 2) - 3)", "-4"),
+            ("2-(3-4)", "(2 - (3 - 4))", "3"),
+            ("2*3+4*5", "((2 * 3) + (4 * 5))", "26"),
+            ("7-6/3%2", "(7 - ((6 / 3) % 2))", "7"),
+            ("1<<2+1", "(1 << (2 + 1))", "8"),
+            ("-8>>>28", "((- 8) >>> 28)", "15"),
+            ("1 < 2 == 2 > 1", "((1 < 2) == (2 > 1))", "true"),
+            ("7 & 3 | 8 ^ 1", "((7 & 3) | (8 ^ 1))", "11"),
+            ("!true || true && false", "((! true) || (true && false))", "false"),
+            ("~0 + 1", "((~ 0) + 1)", "0"),
+            ("i++ + ++i", "((i ++) + (++ i))", "4"),
+            ("-i-- - --i", "((- (i --)) - (-- i))", "0"),
+        ];
+        for (expression, expected, value) in table {
+            let source = format!("i: int = 1;\nx: ? = {};\n", expression);
+            let program = parse_source(&source).unwrap();
+            let Statement::VariableDeclaration(x) = &program.statements[1] else { panic!("expected a variable") };
+            assert_eq!(grouped(&x.value, &source), expected, "{}", expression);
+            let executor = crate::executor::execute(program).unwrap();
+            assert_eq!(executor.variables[1].value.to_string(), value, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn number_literals_must_fit_their_kind() {
+        let literal = |source: &str| match &parse_source(&format!("x: ? = {};\n", source)) {
+            Ok(program) => match &program.statements[0] {
+                Statement::VariableDeclaration(VariableDeclaration { value: Expression::NumberLiteral(literal), .. }) => Ok((literal.value.clone(), literal.kind)),
+                Statement::VariableDeclaration(VariableDeclaration { value: Expression::Unary(unary), .. }) => match &*unary.operand {
+                    Expression::NumberLiteral(literal) => Ok((literal.value.clone(), literal.kind)),
+                    operand => panic!("expected a number, found {:?}", operand),
+                },
+                statement => panic!("expected a variable, found {:?}", statement),
+            },
+            Err(errors) => Err(errors[0].expected.clone()),
+        };
+        assert_eq!(literal("2147483647"), Ok((Number::Integer(2147483647), NumberKind::Int)));
+        assert_eq!(literal("2147483648"), Err(Expected::NumberFitting(NumberKind::Int)));
+        assert_eq!(literal("-2147483648"), Ok((Number::Integer(2147483648), NumberKind::Int)));
+        assert_eq!(literal("-2147483649"), Err(Expected::NumberFitting(NumberKind::Int)));
+        assert_eq!(literal("0xFFFFFFFF"), Ok((Number::Integer(0xFFFFFFFF), NumberKind::Int)));
+        assert_eq!(literal("0x1FFFFFFFF"), Err(Expected::NumberFitting(NumberKind::Int)));
+        assert_eq!(literal("255u8"), Ok((Number::Integer(255), NumberKind::Byte)));
+        assert_eq!(literal("256u8"), Err(Expected::NumberFitting(NumberKind::Byte)));
+        assert_eq!(literal("-32768s"), Ok((Number::Integer(32768), NumberKind::Short)));
+        assert_eq!(literal("1.5f"), Ok((Number::Float(1.5), NumberKind::Float)));
+
+        let source = "a: ? = -2147483648;\nb: ? = 0xFFFFFFFF;\nc: ? = -9223372036854775808i64;\nd: ? = -(2147483647);\n";
+        let executor = crate::executor::execute(parse_source(source).unwrap()).unwrap();
+        let values: Vec<String> = executor.variables.iter().map(|variable| variable.value.to_string()).collect();
+        assert_eq!(values, vec!["-2147483648", "-1", "-9223372036854775808", "-2147483647"]);
+    }
+
+    #[test]
+    fn parsing_the_example_programs_succeeds() {
+        parse_source(include_str!("../tests/var_decl.wly")).unwrap();
+        parse_source(include_str!("../tests/import_test.wly")).unwrap();
+        parse_source(include_str!("../tests/cursed_maps.wly")).unwrap();
+        // Exceptions are not part of the language yet, so the `throw` in the
+        // tour of the language is the only statement it can't parse.
+        assert_eq!(error_lines(include_str!("../tests/everything.wly")), vec![(89, Expected::Statement)]);
+    }
+
+    #[test]
+    fn parsing_reports_every_broken_statement() {
+        let source = "a: int = ;\nb: int = 1;\nc: = 2;\nd: int = 3 4;\ne: int = 5;\n";
+        assert_eq!(
+            error_lines(source),
+            vec![
+                (1, Expected::Expression),
+                (3, Expected::Type),
+                (4, Expected::Token(TokenType::Semicolon)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_recovers_inside_blocks() {
+        let source = "f: func<int> = () => {\n    x: int = ;\n    if (x > ) { return 1; }\n    return 2;\n}\ng: int = ;\n";
+        assert_eq!(
+            error_lines(source),
+            vec![(2, Expected::Expression), (3, Expected::Expression), (6, Expected::Expression)]
+        );
+    }
+
+    #[test]
+    fn parsing_reports_every_error_in_a_lambda_body() {
+        let source = "f: lambda<void> = () => {\n    x: int = ;\n    y: int = ;\n};\nz: int = ;\n";
+        assert_eq!(
+            error_lines(source),
+            vec![(2, Expected::Expression), (3, Expected::Expression), (5, Expected::Expression)]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn blocks_nest_and_hold_map_literals() {
+        let depth: usize = 32;
+        let source = format!(
+            "n: int = 0;\nf: func<int> = () => {{\n    m: map<string, int> = {{ \"a\": 1 }};\n{}n = m[\"a\"];\n{}    return {{ \"b\": 2 }}[\"b\"];\n}}\nx: int = f();\n",
+            "if (true) { ".repeat(depth),
+            "} ".repeat(depth)
+        );
+        let program = parse_source(&source).unwrap();
+        let Statement::FunctionDeclaration(f) = &program.statements[1] else { panic!("expected a function") };
+        let mut statement = &f.body.statements[1];
+        let mut found: usize = 0;
+        while let Statement::If(if_) = statement {
+            found += 1;
+            statement = &if_.body.statements[0];
+        }
+        assert_eq!(found, depth);
+        assert!(matches!(statement, Statement::Assign(_)));
+        assert!(matches!(&f.body.statements[0], Statement::VariableDeclaration(VariableDeclaration { value: Expression::MapLiteral(_), .. })));
+        let executor = crate::executor::execute(program).unwrap();
+        let values: Vec<String> = executor.variables.iter().map(|variable| variable.value.to_string()).collect();
+        assert_eq!(values, vec!["1", "2"]);
+    }
+
+    #[test]
+    fn functions_can_have_an_expression_body() {
+        let source = "identity<T>: func<T> = (x: T) => x;\nC: class = () => {\n    n: int = 0;\n    set: func<void> = (v: int) => this->n = v;\n}\n";
+        let program = parse_source(source).unwrap();
+        let Statement::FunctionDeclaration(identity) = &program.statements[0] else { panic!("expected a function") };
+        assert!(matches!(identity.body.statements[..], [Statement::Return(Return { value: Some(Expression::Identifier(_)), .. })]));
+        let Statement::ClassDeclaration(class) = &program.statements[1] else { panic!("expected a class") };
+        assert!(matches!(class.methods[0].body.statements[..], [Statement::Assign(_)]));
+    }
+
+    #[test]
+    fn type_arguments_must_touch_the_callee_and_parenthesis() {
+        let arguments = |source: &str| match &parse_source(source).unwrap().statements[0] {
+            Statement::Expression(Expression::Call(call)) => (call.type_arguments.len(), call.arguments.len()),
+            statement => panic!("expected a call, found {:?}", statement),
+        };
+        assert_eq!(arguments("f(a < b, c > (d));\n"), (0, 2));
+        assert_eq!(arguments("f(a<b, c>(d));\n"), (0, 1));
+        assert_eq!(arguments("identity<array<int>>(x);\n"), (1, 1));
+        assert_eq!(arguments("s->transform<int>(g);\n"), (1, 1));
+    }
+
+    #[test]
+    fn nested_type_arguments_split_the_closing_tokens() {
+        let source = "a: array<array<int>>= [[1]];\nb: map<string, array<array<int>>> = {};\nc: map<string, array<array<int>>>= {};\nd: lambda<lambda<int>> = () => () => 1;\n";
+        let types: Vec<String> = parse_source(source)
+            .unwrap()
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::VariableDeclaration(variable) => variable.type_.to_string(),
+                statement => panic!("expected a variable, found {:?}", statement),
+            })
+            .collect();
+        assert_eq!(types, vec!["array<array<int>>", "map<string, array<array<int>>>", "map<string, array<array<int>>>", "lambda<lambda<int>>"]);
+        assert_eq!(error_lines("e: array<array<int>>>= [];\n"), vec![(1, Expected::Token(TokenType::Semicolon))]);
+    }
+
+    #[test]
+    fn annotations_operators_and_template_text_keep_their_spans() {
+        let source = "x: array<array<int>>= [[1]];\nf: func<int> = (a: map<string, int>) => { return 1; }\nx += `a${1}b`;\n";
+        let program = parse_source(source).unwrap();
+        let text = |span: Span| &source[span.start_byte..span.end_byte];
+        let Statement::VariableDeclaration(x) = &program.statements[0] else { panic!("expected a variable") };
+        assert_eq!(text(x.type_span), "array<array<int>>");
+        let Statement::FunctionDeclaration(f) = &program.statements[1] else { panic!("expected a function") };
+        assert_eq!(text(f.return_type_span), "func<int>");
+        assert_eq!(text(f.parameters[0].type_span), "map<string, int>");
+        let Statement::Assign(assign) = &program.statements[2] else { panic!("expected an assignment") };
+        assert_eq!(text(assign.operator_span), "+=");
+        let Expression::Template(template) = &assign.value else { panic!("expected a template") };
+        let texts: Vec<&str> = template
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                TemplateElement::Text(_, span) => Some(text(*span)),
+                TemplateElement::Expression(_) => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn interpolations_hold_exactly_one_expression() {
+        let message = |source: &str| parse_source(source).unwrap_err()[0].to_string();
+        assert_eq!(message("x: string = `v ${}`;\n"), "expected an expression, found '}'");
+        assert_eq!(message("x: string = `v ${a b} w`;\n"), "expected CloseBrace, found 'b'");
+        assert_eq!(message("x: string = `v ${a +} w`;\n"), "expected an expression, found '}'");
+    }
+
+    #[test]
+    fn import_names_keep_where_they_were_written() {
+        let source = "const io = import(\"io\");\nexport const { print, println as say } = import(\"io\");\n";
+        let program = parse_source(source).unwrap();
+        let text = |identifier: &Identifier| &source[identifier.span.start_byte..identifier.span.end_byte];
+        let specs: Vec<&ImportSpec> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Import(import) => &import.spec,
+                statement => panic!("expected an import, found {:?}", statement),
+            })
+            .collect();
+        let ImportSpec::Alias(alias) = specs[0] else { panic!("expected an alias") };
+        assert_eq!((alias.name.as_str(), text(alias)), ("io", "io"));
+        let ImportSpec::Named(names) = specs[1] else { panic!("expected names") };
+        let names: Vec<(&str, Option<&str>)> = names.iter().map(|(name, rename)| (text(name), rename.as_ref().map(text))).collect();
+        assert_eq!(names, vec![("print", None), ("println", Some("say"))]);
+    }
+
+    #[test]
+    fn parsing_rejects_declarations_inside_blocks() {
+        let source = "n: namespace => {\n    f: func<int> = () => { return 1; }\n}\ng: func<void> = () => {\n    h: func<int> = () => { return 1; }\n    if (true) { E: enum => A, B; }\n    x: int = 1;\n}\n";
+        assert_eq!(error_lines(source), vec![(5, Expected::TopLevel), (6, Expected::TopLevel)]);
+    }
+
+    #[test]
+    fn parsing_rejects_misplaced_break_and_continue() {
+        let source = "break;\nwhile (true) { break; }\nf: func<void> = () => { continue; }\n";
+        assert_eq!(
+            error_lines(source),
+            vec![(1, Expected::EnclosingLoop), (3, Expected::EnclosingLoop)]
+        );
+    }
+}